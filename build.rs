@@ -20,14 +20,15 @@ fn parse_day_filename(input: &str) -> IResult<&str, i32, ()> {
 }
 
 fn main() {
-    let project_root = env::current_dir().expect("couldn't get working directory");
+    let project_root =
+        PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR not set"));
     let source_directory = project_root.join("src");
 
     println!("cargo:rerun-if-changed={}", source_directory.display());
 
     let items = read_dir(&source_directory).expect("couldn't open the source directory");
 
-    let days: Vec<i32> = items
+    let mut days: Vec<i32> = items
         .map(|item| item.expect("failed to read directory entry"))
         .filter(|item| item.file_type().unwrap().is_file())
         .filter_map(|item| {
@@ -43,39 +44,35 @@ fn main() {
         })
         .collect();
 
+    // read_dir yields entries in an arbitrary, platform-dependent order. Sort
+    // the days so that the generated enum and dispatch are deterministic;
+    // anything that iterates over the days relies on them being in order.
+    days.sort_unstable();
+
     let mods = days
         .iter()
         .map(|day| {
-            // HATE HATE HATE HATE
-            lazy_format!(
-                "#[path = \"../../../../../src/day{day}.rs\"] mod day{day};",
-                day = day
-            )
+            // generated.rs lives somewhere under OUT_DIR, so the module paths
+            // need to be absolute. Use debug formatting to get a correctly
+            // escaped string literal.
+            let path = source_directory.join(format!("day{}.rs", day));
+            lazy_format!("#[path = {path:?}] mod day{day};", path = path, day = day)
         })
         .join_with(Newline);
 
     let enum_variants = days
         .iter()
-        .map(|day| lazy_format!("Day{},", day))
+        .map(|day| lazy_format!("Day{day} = {day},", day = day))
         .join_with(Newline);
 
-    let match_arms = days
+    let day_list = days
         .iter()
-        .map(|day| lazy_format!("{day} => Ok(Day::Day{day}),", day = day))
+        .map(|day| lazy_format!("Day::Day{},", day))
         .join_with(Newline);
 
     let solver_match_arms = days
         .iter()
-        .flat_map(|&day| [(day, 1), (day, 2)])
-        .map(|(day, part)| {
-            lazy_format!(
-                "(Day::Day{day}, Part::Part{part}) => {{
-                    println!(\"{{}}\", day{day}::part{part}(input).context(\"failed to solve puzzle\")?)
-                }}",
-                day=day,
-                part=part
-            )
-        })
+        .map(|day| lazy_format!("Day::Day{day} => &day{day}::Solution,", day = day))
         .join_with(Newline);
 
     let generated_content = lazy_format!(
@@ -87,31 +84,19 @@ fn main() {
             {enum_variants}
         }}
 
-        impl FromStr for Day {{
-            type Err = DayError;
+        const DAYS: &[Day] = &[
+            {day_list}
+        ];
 
-            fn from_str(s: &str) -> Result<Self, Self::Err> {{
-                let value: u8 = s.parse()?;
-
-                match value {{
-                  {match_arms}
-                  value => Err(DayError::BadDay(value)),
-                }}
+        fn solver_for(day: Day) -> &'static dyn Solver {{
+            match day {{
+                {solver_match_arms}
             }}
         }}
-
-
-        fn run_solution(day: Day, part: Part, input: &str) -> anyhow::Result<()> {{
-          match (day, part) {{
-              {solver_match_arms}
-          }}
-
-          Ok(())
-      }}
     ",
         mods = mods,
         enum_variants = enum_variants,
-        match_arms = match_arms,
+        day_list = day_list,
         solver_match_arms = solver_match_arms,
     );
 
@@ -1,29 +1,148 @@
-use anyhow::Context;
-use itertools::Itertools;
+use std::{cmp::Ordering, fmt};
 
-use crate::library::IterExt;
+use anyhow::ensure;
 
-pub fn part1(input: &str) -> anyhow::Result<usize> {
-    let numbers: Vec<i32> = input
-        .split_whitespace()
-        .map(|token| token.parse())
-        .try_collect()
-        .context("failed to parse integer")?;
+use crate::{
+    library::{parse_tokens, IterExt},
+    Part, Solver,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WindowStats {
+    pub increases: usize,
+    pub decreases: usize,
+    pub equal: usize,
+}
+
+impl fmt::Display for WindowStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "increases: {}, decreases: {}, equal: {}",
+            self.increases, self.decreases, self.equal
+        )
+    }
+}
+
+/// Compare the sum of each `window` consecutive readings to the sum of the
+/// `window` readings before it. Adjacent windows share all but their first
+/// and last readings, so only those need to be compared. The readings are
+/// parsed and compared in a single pass, without collecting them.
+pub fn window_stats(input: &str, window: usize) -> anyhow::Result<WindowStats> {
+    ensure!(window > 0, "window size must be at least 1");
+    ensure!(!input.trim().is_empty(), "no readings in input");
 
-    Ok(numbers.windows(2).filter(|pair| pair[0] < pair[1]).count())
+    let stats = parse_tokens::<i64, _>(input.split_whitespace()).use_oks(|readings| {
+        readings.streaming_window_ends(window + 1).fold(
+            WindowStats::default(),
+            |mut stats, (first, last)| {
+                match first.cmp(&last) {
+                    Ordering::Less => stats.increases += 1,
+                    Ordering::Greater => stats.decreases += 1,
+                    Ordering::Equal => stats.equal += 1,
+                }
+                stats
+            },
+        )
+    })?;
+
+    Ok(stats)
+}
+
+/// Count how often the sum of `window` consecutive readings is larger than
+/// the sum of the `window` readings before it
+pub fn count_increases(input: &str, window: usize) -> anyhow::Result<usize> {
+    window_stats(input, window).map(|stats| stats.increases)
+}
+
+pub fn part1(input: &str) -> anyhow::Result<usize> {
+    count_increases(input, 1)
 }
 
 pub fn part2(input: &str) -> anyhow::Result<usize> {
-    input
-        .split_whitespace()
-        .map(|token| token.parse::<u32>())
-        .use_oks(|numbers| {
-            numbers
-                .streaming_windows()
-                .map(|[a, b, c]| a + b + c)
-                .streaming_windows()
-                .filter(|[a, b]| a < b)
-                .count()
-        })
-        .context("failed to parse integer")
+    count_increases(input, 3)
+}
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
+
+    fn details(&self, part: Part, input: &str) -> anyhow::Result<Option<String>> {
+        let window = match part {
+            Part::Part1 => 1,
+            Part::Part2 => 3,
+        };
+
+        window_stats(input, window).map(|stats| Some(stats.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    const SAMPLE: &str = "199 200 208 210 200 207 240 269 260 263";
+
+    #[test]
+    fn test_window_sizes() {
+        assert_eq!(count_increases(SAMPLE, 1).unwrap(), 7);
+        assert_eq!(count_increases(SAMPLE, 3).unwrap(), 5);
+        assert_eq!(
+            window_stats(SAMPLE, 3).unwrap().increases,
+            part2(SAMPLE).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_streaming_part1() {
+        // Generate a long, bumpy sequence of readings
+        let readings: Vec<i64> = (0..100_000i64)
+            .map(|i| (i * 7919) % 1000 + i / 10)
+            .collect();
+        let input = readings.iter().join("\n");
+
+        // The original, collecting implementations
+        let part1_increases = readings.windows(2).filter(|pair| pair[0] < pair[1]).count();
+        let part2_increases = readings
+            .windows(3)
+            .map(|window| window.iter().sum::<i64>())
+            .tuple_windows()
+            .filter(|(a, b)| a < b)
+            .count();
+
+        assert_eq!(part1(&input).unwrap(), part1_increases);
+        assert_eq!(part2(&input).unwrap(), part2_increases);
+    }
+
+    #[test]
+    fn test_window_stats() {
+        let stats = window_stats(SAMPLE, 3).unwrap();
+        let num_windows = 10 - 3;
+
+        assert_eq!(stats.increases, 5);
+        assert_eq!(stats.increases + stats.decreases + stats.equal, num_windows);
+    }
+
+    #[test]
+    fn test_bad_token() {
+        let input = "199 200 208 2l0 200 207";
+        let expected = r#"failed to parse token "2l0" at index 3"#;
+
+        assert_eq!(part1(input).unwrap_err().to_string(), expected);
+        assert_eq!(part2(input).unwrap_err().to_string(), expected);
+        assert_eq!(window_stats(input, 2).unwrap_err().to_string(), expected);
+    }
+
+    #[test]
+    fn test_window_larger_than_input() {
+        assert_eq!(window_stats(SAMPLE, 20).unwrap().increases, 0);
+    }
 }
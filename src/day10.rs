@@ -1,179 +1,651 @@
-use std::iter;
+use std::fmt;
 
+use anyhow::{ensure, Context};
 use itertools::Itertools;
-use nom::{
-    branch::alt,
-    character::complete::char,
-    combinator::{eof, success},
-    multi::fold_many0,
-    IResult, Parser,
-};
-use nom_supreme::{
-    error::{BaseErrorKind, ErrorTree, StackContext},
-    final_parser::final_parser,
-    multi::collect_separated_terminated,
-    ParserExt,
-};
 
-fn chunk_parser<'a>(start: char, end: char) -> impl Parser<&'a str, (), ErrorTree<&'a str>> {
-    fold_many0(parse_chunk, || (), |(), ()| ())
-        .terminated(char(end).context("end"))
-        .cut()
-        .preceded_by(char(start))
-}
+use crate::{Part, Solver};
 
-fn parse_chunk(input: &str) -> IResult<&str, (), ErrorTree<&str>> {
-    alt((
-        chunk_parser('(', ')').context("p"),
-        chunk_parser('{', '}').context("c"),
-        chunk_parser('[', ']').context("s"),
-        chunk_parser('<', '>').context("a"),
-    ))
-    .parse(input)
+/// The first illegal character on a corrupted line: a closing character that
+/// doesn't match the chunk it's closing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Corruption {
+    pub expected: char,
+    pub found: char,
 }
 
-fn parse_line(input: &str) -> IResult<&str, (), ErrorTree<&str>> {
-    collect_separated_terminated(parse_chunk, success(()), eof).parse(input)
+impl fmt::Display for Corruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Expected {}, but found {}", self.expected, self.found)
+    }
 }
 
-fn final_parse_line(input: &str) -> Result<(), ErrorTree<&str>> {
-    final_parser(parse_line)(input)
+/// The result of scanning a line of brackets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LineStatus {
+    /// Every chunk on the line is closed
+    Valid,
+
+    /// A chunk was closed with the wrong character
+    Corrupt(Corruption),
+
+    /// The line ended with some chunks still open. Contains the closing
+    /// characters needed to complete it, in order.
+    Incomplete(Vec<char>),
+
+    /// The line contains a character that isn't an opening or closing
+    /// character, or a closing character without a chunk to close
+    Invalid(char),
 }
 
+/// A set of bracket pairs, along with the score for each closing character
+/// when it's found on a corrupted line or needed to complete a line
 #[derive(Debug, Clone, Copy)]
-struct ContextView<'a, I> {
-    context: &'a [(I, StackContext)],
-    parent: Option<&'a ContextView<'a, I>>,
+pub struct Brackets<'a> {
+    /// The (opening, closing) character for each kind of chunk
+    pub pairs: &'a [(char, char)],
+
+    /// The score for finding each closing character on a corrupted line
+    pub corruption_scores: &'a [(char, i64)],
+
+    /// The score for each closing character needed to complete a line
+    pub completion_scores: &'a [(char, i64)],
 }
 
-impl<'a, I> ContextView<'a, I> {
-    fn empty() -> Self {
-        Self {
-            context: &[],
-            parent: None,
-        }
+/// The (opening, closing) character for each kind of chunk in the puzzle
+pub const BRACKET_PAIRS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')];
+
+/// The score for finding each closing character on a corrupted line
+pub const CORRUPTION_SCORES: [(char, i64); 4] = [(')', 3), (']', 57), ('}', 1197), ('>', 25137)];
+
+/// The score for each closing character needed to complete a line
+pub const COMPLETION_SCORES: [(char, i64); 4] = [(')', 1), (']', 2), ('}', 3), ('>', 4)];
+
+/// The brackets from the puzzle
+pub const STANDARD_BRACKETS: Brackets<'static> = Brackets {
+    pairs: &BRACKET_PAIRS,
+    corruption_scores: &CORRUPTION_SCORES,
+    completion_scores: &COMPLETION_SCORES,
+};
+
+/// Look up a character's score, or 0 if it doesn't have one
+fn lookup_score(scores: &[(char, i64)], c: char) -> i64 {
+    scores
+        .iter()
+        .find(|&&(scored, _)| scored == c)
+        .map(|&(_, score)| score)
+        .unwrap_or(0)
+}
+
+impl Brackets<'_> {
+    /// Get the closing character for an opening character, or None if this
+    /// isn't an opening character
+    fn closer_for(&self, c: char) -> Option<char> {
+        self.pairs
+            .iter()
+            .find(|&&(open, _)| open == c)
+            .map(|&(_, close)| close)
+    }
+
+    fn is_closer(&self, c: char) -> bool {
+        self.pairs.iter().any(|&(_, close)| close == c)
     }
 
-    pub fn contains_context(&self, context: &str) -> bool {
-        self.context.iter().any(|(_, c)| match *c {
-            StackContext::Context(c) => context == c,
-            _ => false,
-        }) || self
-            .parent
-            .map(|parent| parent.contains_context(context))
-            .unwrap_or(false)
+    /// Scan a line, keeping a stack of the closing characters expected for
+    /// each of the currently open chunks
+    pub fn scan_line(&self, line: &str) -> LineStatus {
+        let mut expected_closers = Vec::new();
+
+        for c in line.chars() {
+            if let Some(closer) = self.closer_for(c) {
+                expected_closers.push(closer);
+                continue;
+            }
+
+            match expected_closers.pop() {
+                Some(expected) if expected == c => {}
+                Some(expected) if self.is_closer(c) => {
+                    return LineStatus::Corrupt(Corruption { expected, found: c })
+                }
+                _ => return LineStatus::Invalid(c),
+            }
+        }
+
+        match expected_closers.is_empty() {
+            true => LineStatus::Valid,
+            false => {
+                expected_closers.reverse();
+                LineStatus::Incomplete(expected_closers)
+            }
+        }
     }
 
-    pub fn iter_context_frames(&self) -> impl Iterator<Item = &'a [(I, StackContext)]> + '_ {
-        let mut this = Some(self);
+    /// Score the first illegal character on a corrupted line
+    pub fn corruption_score(&self, found: char) -> i64 {
+        lookup_score(self.corruption_scores, found)
+    }
 
-        iter::from_fn(move || {
-            let &ContextView {
-                parent: next,
-                context: frame,
-            } = this?;
-            this = next;
-            Some(frame)
+    /// Score the closing characters needed to complete a line
+    pub fn completion_score(&self, closers: &[char]) -> i64 {
+        closers.iter().fold(0, |score, &closer| {
+            score * 5 + lookup_score(self.completion_scores, closer)
         })
     }
 
-    /// Iterate all contexts, in reverse order
-    pub fn iter(&self) -> impl Iterator<Item = (&'a I, &'a StackContext)> + '_ {
-        self.iter_context_frames()
-            .flat_map(|frame| frame.iter().map(|(loc, ctx)| (loc, ctx)).rev())
+    /// Scan every line of the input in a single pass. Trailing whitespace on
+    /// each line is ignored, and blank lines are skipped entirely.
+    pub fn report(&self, input: &str) -> LineReport {
+        let mut report = LineReport::default();
+
+        for (index, line) in input.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() {
+                continue;
+            }
+
+            match self.scan_line(line) {
+                LineStatus::Valid => report.valid.push(index),
+                LineStatus::Corrupt(corruption) => report.corrupt.push((index, corruption.found)),
+                LineStatus::Incomplete(closers) => report
+                    .incomplete
+                    .push((index, self.completion_score(&closers))),
+                LineStatus::Invalid(c) => report.invalid.push((index, c)),
+            }
+        }
+
+        report
+    }
+
+    /// The total score of all the corrupted lines in the input
+    pub fn total_corruption_score(&self, input: &str) -> i64 {
+        self.report(input)
+            .corrupt
+            .iter()
+            .map(|&(_, found)| self.corruption_score(found))
+            .sum()
+    }
+
+    /// The middle score of all the incomplete lines in the input
+    pub fn middle_completion_score(&self, input: &str) -> anyhow::Result<i64> {
+        self.report(input).middle_completion_score()
     }
 }
 
-fn visit_error<I>(
-    err: &ErrorTree<I>,
-    visitor: &mut dyn for<'a> FnMut(&'a I, &'a BaseErrorKind, ContextView<'a, I>),
-) {
-    match err {
-        ErrorTree::Base { location, kind } => visitor(location, kind, ContextView::empty()),
-        ErrorTree::Stack { base, contexts } => visit_error(base, &mut |location, kind, ctx| {
-            let ctx = ContextView {
-                context: contexts,
-                parent: Some(&ctx),
-            };
-
-            visitor(location, kind, ctx);
-        }),
-        ErrorTree::Alt(branches) => branches.iter().for_each(|err| visit_error(err, visitor)),
+/// The classification of every line in an input, by line index
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineReport {
+    /// The lines where every chunk is closed
+    pub valid: Vec<usize>,
+
+    /// The corrupted lines, with the first illegal character on each
+    pub corrupt: Vec<(usize, char)>,
+
+    /// The incomplete lines, with the score of the characters needed to
+    /// complete each
+    pub incomplete: Vec<(usize, i64)>,
+
+    /// The lines containing a character that isn't part of any chunk
+    pub invalid: Vec<(usize, char)>,
+}
+
+impl LineReport {
+    /// The middle score of the incomplete lines
+    pub fn middle_completion_score(&self) -> anyhow::Result<i64> {
+        let scores = self
+            .incomplete
+            .iter()
+            .map(|&(_, score)| score)
+            .sorted_unstable()
+            .collect_vec();
+
+        scores
+            .get(scores.len() / 2)
+            .copied()
+            .context("no incomplete lines in input")
     }
 }
 
-pub fn part1(input: &str) -> anyhow::Result<usize> {
-    Ok(input
-        .lines()
-        .map(|line| match final_parse_line(line) {
-            Ok(()) => {
-                eprintln!("Unexpectedly good line");
-                0
-            }
-            Err(err) => {
-                let mut score = 0;
-
-                visit_error(&err, &mut |tail, _, ctx| {
-                    if !ctx.contains_context("end") {
-                        return;
-                    }
-
-                    if let Some(c) = tail.chars().next() {
-                        score = match c {
-                            ')' => 3,
-                            ']' => 57,
-                            '}' => 1197,
-                            '>' => 25137,
-                            _ => 0,
-                        };
-                    }
-                });
+/// Scan a line of the puzzle's brackets
+pub fn scan_line(line: &str) -> LineStatus {
+    STANDARD_BRACKETS.scan_line(line)
+}
 
-                score
-            }
+/// Find the first illegal character on each line, if it's corrupted
+pub fn corruptions(input: &str) -> Vec<Option<Corruption>> {
+    input
+        .lines()
+        .map(|line| match scan_line(line.trim_end()) {
+            LineStatus::Corrupt(corruption) => Some(corruption),
+            _ => None,
         })
-        .sum())
+        .collect()
+}
+
+pub fn part1(input: &str) -> anyhow::Result<i64> {
+    ensure!(!input.trim().is_empty(), "no lines in input");
+
+    Ok(STANDARD_BRACKETS.total_corruption_score(input))
 }
 
 pub fn part2(input: &str) -> anyhow::Result<i64> {
-    let mut scores = input
-        .lines()
-        .filter_map(|line| match final_parse_line(line) {
-            Ok(()) => {
-                eprintln!("Unexpectedly good line");
-                None
+    STANDARD_BRACKETS.middle_completion_score(input)
+}
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
+
+    fn details(&self, part: Part, input: &str) -> anyhow::Result<Option<String>> {
+        Ok(match part {
+            Part::Part1 => Some(
+                corruptions(input)
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, corruption)| {
+                        Some(format!("line {}: {}", index + 1, corruption.as_ref()?))
+                    })
+                    .join("\n"),
+            ),
+            Part::Part2 => Some(
+                STANDARD_BRACKETS
+                    .report(input)
+                    .incomplete
+                    .iter()
+                    .map(|(index, score)| format!("line {}: {}", index + 1, score))
+                    .join("\n"),
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::iter;
+
+    use nom::{
+        branch::alt,
+        character::complete::char,
+        combinator::{eof, success},
+        multi::fold_many0,
+        IResult, Parser,
+    };
+    use nom_supreme::{
+        error::{BaseErrorKind, ErrorTree, Expectation, StackContext},
+        final_parser::final_parser,
+        multi::collect_separated_terminated,
+        ParserExt,
+    };
+    use proptest::prelude::*;
+
+    // The original implementation, which parses each line with nom and
+    // recovers the corruption or completion from the error tree
+
+    fn chunk_parser<'a>(start: char, end: char) -> impl Parser<&'a str, (), ErrorTree<&'a str>> {
+        fold_many0(parse_chunk, || (), |(), ()| ())
+            .terminated(char(end).context("end"))
+            .cut()
+            .preceded_by(char(start))
+    }
+
+    fn parse_chunk(input: &str) -> IResult<&str, (), ErrorTree<&str>> {
+        alt((
+            chunk_parser('(', ')').context(")"),
+            chunk_parser('{', '}').context("}"),
+            chunk_parser('[', ']').context("]"),
+            chunk_parser('<', '>').context(">"),
+        ))
+        .parse(input)
+    }
+
+    fn parse_line(input: &str) -> IResult<&str, (), ErrorTree<&str>> {
+        collect_separated_terminated(parse_chunk, success(()), eof).parse(input)
+    }
+
+    fn final_parse_line(input: &str) -> Result<(), ErrorTree<&str>> {
+        final_parser(parse_line)(input)
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    struct ContextView<'a, I> {
+        context: &'a [(I, StackContext)],
+        parent: Option<&'a ContextView<'a, I>>,
+    }
+
+    impl<'a, I> ContextView<'a, I> {
+        fn empty() -> Self {
+            Self {
+                context: &[],
+                parent: None,
+            }
+        }
+
+        pub fn contains_context(&self, context: &str) -> bool {
+            self.context.iter().any(|(_, c)| match *c {
+                StackContext::Context(c) => context == c,
+                _ => false,
+            }) || self
+                .parent
+                .map(|parent| parent.contains_context(context))
+                .unwrap_or(false)
+        }
+
+        pub fn iter_context_frames(&self) -> impl Iterator<Item = &'a [(I, StackContext)]> + '_ {
+            let mut this = Some(self);
+
+            iter::from_fn(move || {
+                let &ContextView {
+                    parent: next,
+                    context: frame,
+                } = this?;
+                this = next;
+                Some(frame)
+            })
+        }
+
+        /// Iterate all contexts, in reverse order
+        pub fn iter(&self) -> impl Iterator<Item = (&'a I, &'a StackContext)> + '_ {
+            self.iter_context_frames()
+                .flat_map(|frame| frame.iter().map(|(loc, ctx)| (loc, ctx)).rev())
+        }
+    }
+
+    fn visit_error<I>(
+        err: &ErrorTree<I>,
+        visitor: &mut dyn for<'a> FnMut(&'a I, &'a BaseErrorKind, ContextView<'a, I>),
+    ) {
+        match err {
+            ErrorTree::Base { location, kind } => visitor(location, kind, ContextView::empty()),
+            ErrorTree::Stack { base, contexts } => visit_error(base, &mut |location, kind, ctx| {
+                let ctx = ContextView {
+                    context: contexts,
+                    parent: Some(&ctx),
+                };
+
+                visitor(location, kind, ctx);
+            }),
+            ErrorTree::Alt(branches) => branches.iter().for_each(|err| visit_error(err, visitor)),
+        }
+    }
+
+    /// Find the first illegal character on a line, or None if the line is valid
+    /// or merely incomplete
+    fn nom_corruption(line: &str) -> Option<Corruption> {
+        let err = final_parse_line(line).err()?;
+        let mut corruption = None;
+
+        visit_error(&err, &mut |tail, kind, ctx| {
+            if !ctx.contains_context("end") {
+                return;
             }
-            Err(err) => {
-                let mut score: Option<i64> = None;
-
-                visit_error(&err, &mut |tail, _, ctx| {
-                    if !ctx.contains_context("end") {
-                        return;
-                    }
-
-                    if tail.is_empty() {
-                        score = Some(
-                            ctx.iter()
-                                .filter_map(|(_, ctx)| match ctx {
-                                    StackContext::Context("p") => Some(1),
-                                    StackContext::Context("s") => Some(2),
-                                    StackContext::Context("c") => Some(3),
-                                    StackContext::Context("a") => Some(4),
-                                    _ => None,
-                                })
-                                .enumerate()
-                                .map(|(idx, digit)| digit * 5i64.pow(idx as u32))
-                                .sum(),
-                        )
-                    }
+
+            if let (Some(found), BaseErrorKind::Expected(Expectation::Char(expected))) =
+                (tail.chars().next(), kind)
+            {
+                corruption = Some(Corruption {
+                    expected: *expected,
+                    found,
                 });
+            }
+        });
+
+        corruption
+    }
+
+    /// Score the completion of a line by finding which chunks were still open
+    /// when the parser ran out of input
+    fn nom_completion_score(line: &str) -> Option<i64> {
+        let err = final_parse_line(line).err()?;
+        let mut score: Option<i64> = None;
 
-                score
+        visit_error(&err, &mut |tail, _, ctx| {
+            if !ctx.contains_context("end") {
+                return;
             }
-        })
-        .collect_vec();
 
-    scores.sort_unstable();
+            if tail.is_empty() {
+                score = Some(
+                    ctx.iter()
+                        .filter_map(|(_, ctx)| match ctx {
+                            StackContext::Context(closer) => COMPLETION_SCORES
+                                .iter()
+                                .find(|&&(scored, _)| closer.starts_with(scored))
+                                .map(|&(_, score)| score),
+                            _ => None,
+                        })
+                        .enumerate()
+                        .map(|(idx, digit)| digit * 5i64.pow(idx as u32))
+                        .sum(),
+                )
+            }
+        });
+
+        score
+    }
+
+    /// Check the stack scanner against the nom parser
+    fn check_against_nom(line: &str) {
+        let (corruption, completion) = match scan_line(line) {
+            LineStatus::Corrupt(corruption) => (Some(corruption), None),
+            LineStatus::Incomplete(closers) => {
+                (None, Some(STANDARD_BRACKETS.completion_score(&closers)))
+            }
+            LineStatus::Valid => {
+                assert!(
+                    final_parse_line(line).is_ok() || line.is_empty(),
+                    "{:?}",
+                    line
+                );
+                (None, None)
+            }
+            LineStatus::Invalid(_) => (None, None),
+        };
+
+        assert_eq!(corruption, nom_corruption(line), "{:?}", line);
+        assert_eq!(completion, nom_completion_score(line), "{:?}", line);
+    }
+
+    const SAMPLE: &str = "\
+[({(<(())[]>[[{[]{<()<>>
+[(()[<>])]({[<{<<[]>>(
+{([(<{}[<>[]}>{[]{[(<()>
+(((({<>}<{<{<>}{[]{[]{}
+[[<[([]))<([[{}[[()]]]
+[{[{({}]{}}([{[{{{}}([]
+{<[[]]>}<{[{[{[]{()[[[]
+[<(<(<(<{}))><([]([]()
+<{([([[(<>()){}]>(<<{{
+<{([{{}}[<[[[<>{}]]]>[]]
+";
+
+    #[test]
+    fn test_sample() {
+        assert_eq!(part1(SAMPLE).unwrap(), 26397);
+        assert_eq!(part2(SAMPLE).unwrap(), 288957);
+    }
+
+    #[test]
+    fn test_corruptions() {
+        let corruptions = corruptions(SAMPLE);
+        let corrupt_lines = corruptions
+            .iter()
+            .enumerate()
+            .filter_map(|(index, corruption)| Some((index, corruption.as_ref()?.to_string())))
+            .collect_vec();
+
+        assert_eq!(
+            corrupt_lines,
+            [
+                (2, "Expected ], but found }".to_owned()),
+                (4, "Expected ], but found )".to_owned()),
+                (5, "Expected ), but found ]".to_owned()),
+                (7, "Expected >, but found )".to_owned()),
+                (8, "Expected ], but found >".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_line_status() {
+        assert_eq!(scan_line("([]){<>}"), LineStatus::Valid);
+        assert_eq!(scan_line(""), LineStatus::Valid);
+        assert_eq!(
+            scan_line("{([(<{}[<>[]}>{[]{[(<()>"),
+            LineStatus::Corrupt(Corruption {
+                expected: ']',
+                found: '}'
+            })
+        );
+        assert_eq!(
+            scan_line("[({(<(())[]>[[{[]{<()<>>"),
+            LineStatus::Incomplete("}}]])})]".chars().collect())
+        );
+        assert_eq!(scan_line("()]"), LineStatus::Invalid(']'));
+        assert_eq!(scan_line("(x)"), LineStatus::Invalid('x'));
+    }
+
+    #[test]
+    fn test_sample_matches_nom() {
+        SAMPLE.lines().for_each(check_against_nom);
+    }
+
+    /// Build a line that's valid or incomplete: each step either opens a
+    /// chunk or correctly closes the innermost open one
+    fn build_line(steps: &[(usize, bool)]) -> String {
+        let mut line = String::new();
+        let mut open = Vec::new();
+
+        for &(bracket, opening) in steps {
+            if opening {
+                let (opener, closer) = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')][bracket];
+                line.push(opener);
+                open.push(closer);
+            } else if let Some(closer) = open.pop() {
+                line.push(closer);
+            }
+        }
+
+        line
+    }
+
+    proptest! {
+        #[test]
+        fn test_random_lines_match_nom(line in "[\\(\\)\\[\\]\\{\\}<>]{1,30}") {
+            check_against_nom(&line);
+        }
+
+        #[test]
+        fn test_well_formed_lines_match_nom(
+            steps in prop::collection::vec((0usize..4, any::<bool>()), 1..40),
+            corrupt in prop::option::of(0usize..4),
+        ) {
+            let mut line = build_line(&steps);
+            if let Some(bracket) = corrupt {
+                line.push([')', ']', '}', '>'][bracket]);
+            }
+
+            check_against_nom(&line);
+        }
+    }
+
+    #[test]
+    fn test_custom_brackets() {
+        let brackets = Brackets {
+            pairs: &[('(', ')'), ('«', '»')],
+            corruption_scores: &[(')', 10), ('»', 100)],
+            completion_scores: &[(')', 1), ('»', 2)],
+        };
 
-    Ok(scores[scores.len() / 2])
+        assert_eq!(brackets.scan_line("(«»)"), LineStatus::Valid);
+        assert_eq!(
+            brackets.scan_line("(«)"),
+            LineStatus::Corrupt(Corruption {
+                expected: '»',
+                found: ')'
+            })
+        );
+        assert_eq!(
+            brackets.scan_line("((«"),
+            LineStatus::Incomplete(vec!['»', ')', ')'])
+        );
+
+        // The standard brackets aren't part of this set
+        assert_eq!(brackets.scan_line("([])"), LineStatus::Invalid('['));
+
+        let input = "(«)\n«(»\n(«»»\n((«\n«\n";
+        assert_eq!(brackets.total_corruption_score(input), 10 + 100 + 100);
+
+        // Completion scores are 2*25 + 1*5 + 1 = 56 and 2
+        assert_eq!(brackets.completion_score(&['»', ')', ')']), 56);
+        assert_eq!(brackets.middle_completion_score(input).unwrap(), 56);
+    }
+
+    #[test]
+    fn test_report() {
+        let report = STANDARD_BRACKETS.report(SAMPLE);
+
+        assert_eq!(report.valid, []);
+        assert_eq!(
+            report.corrupt,
+            [(2, '}'), (4, ')'), (5, ']'), (7, ')'), (8, '>')]
+        );
+        assert_eq!(
+            report.incomplete,
+            [(0, 288957), (1, 5566), (3, 1480781), (6, 995444), (9, 294)]
+        );
+        assert_eq!(report.invalid, []);
+        assert_eq!(report.middle_completion_score().unwrap(), 288957);
+    }
+
+    #[test]
+    fn test_report_whitespace() {
+        let report = STANDARD_BRACKETS.report("()  \n\n[<\r\n(]\t\n");
+
+        assert_eq!(report.valid, [0]);
+        assert_eq!(report.corrupt, [(3, ']')]);
+        assert_eq!(report.incomplete, [(2, 4 * 5 + 2)]);
+        assert_eq!(report.invalid, []);
+    }
+
+    #[test]
+    fn test_score_tables() {
+        assert_eq!(
+            CORRUPTION_SCORES,
+            [(')', 3), (']', 57), ('}', 1197), ('>', 25137)]
+        );
+        assert_eq!(COMPLETION_SCORES, [(')', 1), (']', 2), ('}', 3), ('>', 4)]);
+
+        // Every closing character has both scores
+        for (_, close) in BRACKET_PAIRS {
+            assert!(CORRUPTION_SCORES.iter().any(|&(c, _)| c == close));
+            assert!(COMPLETION_SCORES.iter().any(|&(c, _)| c == close));
+        }
+
+        // The example completion from the puzzle, "])}>", scores 294
+        assert_eq!(
+            STANDARD_BRACKETS.completion_score(&[']', ')', '}', '>']),
+            294
+        );
+    }
+
+    #[test]
+    fn test_blank_lines() {
+        let spaced = SAMPLE.lines().flat_map(|line| [line, "", "   "]).join("\n");
+
+        assert_eq!(part1(&spaced).unwrap(), 26397);
+        assert_eq!(part2(&spaced).unwrap(), 288957);
+
+        let report = STANDARD_BRACKETS.report(&spaced);
+        assert_eq!(report.valid, []);
+        assert_eq!(report.invalid, []);
+        assert_eq!(report.corrupt.len(), 5);
+        assert_eq!(report.incomplete.len(), 5);
+    }
 }
@@ -1,12 +1,26 @@
-use std::{collections::HashSet, iter};
+use std::{collections::HashSet, fmt, iter};
 
-use anyhow::Context;
+use anyhow::{ensure, Context};
 use gridly::prelude::{GridBounds, GridMut, GridSetter, Location, TOUCHING_ADJACENCIES};
 use gridly_grids::ArrayGrid;
 use itertools::Itertools;
 
-struct OctopusGrid {
-    grid: ArrayGrid<i64, 10, 10>,
+use crate::{
+    grid::{neighbors_with_diagonals, parse_digit_array_grid, render_grid_with_locations},
+    Part, Solver,
+};
+
+/// An octopus flashes when its energy level reaches this threshold
+pub const FLASH_THRESHOLD: i64 = 10;
+
+/// An R×C grid of octopuses
+struct OctopusGrid<const R: usize, const C: usize> {
+    grid: ArrayGrid<i64, R, C>,
+    threshold: i64,
+
+    /// If set, the grid wraps around, so octopuses on the edges are neighbors
+    /// of the octopuses on the opposite edge
+    wrapping: bool,
 
     // Store the buffers used in `take_step` so that they can be reused over
     // several steps
@@ -14,11 +28,33 @@ struct OctopusGrid {
     flash_buffer: HashSet<Location>,
 }
 
-impl OctopusGrid {
-    fn from_rows(rows: [[i64; 10]; 10]) -> Self {
+/// The neighbors of `location`, including diagonals, on an R×C grid that
+/// wraps around at the edges. On grids narrower than 3 cells, the same
+/// neighbor can be reached in more than one direction; it's only included
+/// once, and `location` is never its own neighbor.
+fn wrapping_neighbors<const R: usize, const C: usize>(
+    location: Location,
+) -> impl Iterator<Item = Location> {
+    TOUCHING_ADJACENCIES
+        .iter()
+        .map(move |&direction| location + direction)
+        .map(|neighbor| {
+            Location::new(
+                neighbor.row.0.rem_euclid(R as isize),
+                neighbor.column.0.rem_euclid(C as isize),
+            )
+        })
+        .filter(move |&neighbor| neighbor != location)
+        .unique()
+}
+
+impl<const R: usize, const C: usize> OctopusGrid<R, C> {
+    fn new(grid: ArrayGrid<i64, R, C>, threshold: i64, wrapping: bool) -> Self {
         Self {
-            grid: ArrayGrid::from_rows(rows),
-            increment_buffer: Vec::with_capacity(100),
+            grid,
+            threshold,
+            wrapping,
+            increment_buffer: Vec::with_capacity(R * C),
             flash_buffer: HashSet::new(),
         }
     }
@@ -39,16 +75,21 @@ impl OctopusGrid {
         while let Some(location) = self.increment_buffer.pop() {
             if let Ok(cell) = self.grid.get_mut(location) {
                 *cell += 1;
-                if *cell == 10 {
-                    // Record a flash
-                    self.flash_buffer.insert(location);
 
+                // An octopus flashes at most once per step, no matter how far
+                // past the threshold its energy goes. The flash buffer records
+                // which octopuses have already flashed on this step.
+                if *cell >= self.threshold && self.flash_buffer.insert(location) {
                     // All adjacent octopuses will increment again
-                    self.increment_buffer.extend(
-                        TOUCHING_ADJACENCIES
-                            .iter()
-                            .map(|&direction| location + direction),
-                    )
+                    match self.wrapping {
+                        false => self.increment_buffer.extend(
+                            neighbors_with_diagonals(&self.grid, location)
+                                .map(|(neighbor, _)| neighbor),
+                        ),
+                        true => self
+                            .increment_buffer
+                            .extend(wrapping_neighbors::<R, C>(location)),
+                    }
                 }
             }
         }
@@ -62,34 +103,344 @@ impl OctopusGrid {
     }
 }
 
-fn parse_grid(input: &str) -> anyhow::Result<OctopusGrid> {
-    brownstone::try_build_iter(
-        input
-            .lines()
-            .map(|line| {
-                brownstone::try_build_iter(
-                    line.chars()
-                        .map(|c| c.to_digit(10).map(|d| d as i64))
-                        .while_some(),
-                )
-            })
-            .while_some(),
-    )
-    .context("failed to build grid")
-    .map(OctopusGrid::from_rows)
+/// Octopuses that flashed on the most recent step are rendered as `*`
+impl<const R: usize, const C: usize> fmt::Display for OctopusGrid<R, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rendered = render_grid_with_locations(&self.grid, |location, &energy| {
+            match self.flash_buffer.contains(&location) {
+                true => '*',
+                false => char::from_digit(energy as u32, 10).unwrap_or('+'),
+            }
+        });
+
+        f.write_str(&rendered)
+    }
 }
 
-pub fn part1(input: &str) -> anyhow::Result<usize> {
-    let mut grid = parse_grid(input)?;
+fn parse_grid<const R: usize, const C: usize>(
+    input: &str,
+    threshold: i64,
+    wrapping: bool,
+) -> anyhow::Result<OctopusGrid<R, C>> {
+    ensure!(threshold > 0, "the flash threshold must be at least 1");
 
-    Ok((0..100).map(move |_| grid.take_step()).sum())
+    parse_digit_array_grid(input)
+        .context("failed to build grid")
+        .map(|grid| OctopusGrid::new(grid, threshold, wrapping))
 }
 
-pub fn part2(input: &str) -> anyhow::Result<usize> {
-    let mut grid = parse_grid(input)?;
+/// The number of flashes on each of the first `steps` steps on an R×C grid,
+/// where octopuses flash when their energy reaches `threshold`. If `wrapping`
+/// is set, the grid wraps around at the edges.
+pub fn flash_counts_with<const R: usize, const C: usize>(
+    input: &str,
+    steps: usize,
+    threshold: i64,
+    wrapping: bool,
+) -> anyhow::Result<Vec<usize>> {
+    let mut grid = parse_grid::<R, C>(input, threshold, wrapping)?;
+
+    Ok((0..steps).map(move |_| grid.take_step()).collect())
+}
+
+/// The number of flashes on each of the first `steps` steps on an R×C grid
+pub fn flash_counts<const R: usize, const C: usize>(
+    input: &str,
+    steps: usize,
+) -> anyhow::Result<Vec<usize>> {
+    flash_counts_with::<R, C>(input, steps, FLASH_THRESHOLD, false)
+}
+
+/// The total number of flashes over the first `steps` steps on an R×C grid
+pub fn total_flashes<const R: usize, const C: usize>(
+    input: &str,
+    steps: usize,
+) -> anyhow::Result<usize> {
+    flash_counts::<R, C>(input, steps).map(|counts| counts.iter().sum())
+}
+
+/// Render the R×C grid before the first step and after each of the first
+/// `steps` steps
+pub fn animate<const R: usize, const C: usize>(
+    input: &str,
+    steps: usize,
+) -> anyhow::Result<Vec<String>> {
+    let mut grid = parse_grid::<R, C>(input, FLASH_THRESHOLD, false)?;
+    let initial = grid.to_string();
+
+    Ok(iter::once(initial)
+        .chain((0..steps).map(|_| {
+            grid.take_step();
+            grid.to_string()
+        }))
+        .collect())
+}
+
+/// The first step on which every octopus on an R×C grid flashes
+pub fn first_synchronized_step<const R: usize, const C: usize>(
+    input: &str,
+) -> anyhow::Result<usize> {
+    let mut grid = parse_grid::<R, C>(input, FLASH_THRESHOLD, false)?;
 
     iter::repeat_with(|| grid.take_step())
-        .position(|flash_count| flash_count == 100)
+        .position(|flash_count| flash_count == R * C)
         .map(|step| step + 1)
         .context("infinite iterator wasn't infinite :(")
 }
+
+/// Solve both parts in a single simulation, returning the total flashes over
+/// the first 100 steps and the first step on which every octopus flashes.
+/// The simulation continues until both are known, so the synchronized step
+/// may come before or after step 100.
+fn solve_both(input: &str) -> anyhow::Result<(usize, usize)> {
+    let mut grid = parse_grid::<10, 10>(input, FLASH_THRESHOLD, false)?;
+    let mut total_flashes = 0;
+    let mut synchronized_step = None;
+
+    for step in 1.. {
+        let flash_count = grid.take_step();
+
+        if step <= 100 {
+            total_flashes += flash_count;
+        }
+
+        if synchronized_step.is_none() && flash_count == 10 * 10 {
+            synchronized_step = Some(step);
+        }
+
+        if let Some(synchronized_step) = synchronized_step {
+            if step >= 100 {
+                return Ok((total_flashes, synchronized_step));
+            }
+        }
+    }
+
+    unreachable!("infinite loop wasn't infinite :(")
+}
+
+pub fn part1(input: &str) -> anyhow::Result<usize> {
+    total_flashes::<10, 10>(input, 100)
+}
+
+pub fn part2(input: &str) -> anyhow::Result<usize> {
+    first_synchronized_step::<10, 10>(input)
+}
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
+
+    fn both(&self, input: &str) -> anyhow::Result<(String, String)> {
+        let (part1, part2) = solve_both(input)?;
+        Ok((part1.to_string(), part2.to_string()))
+    }
+
+    fn details(&self, part: Part, input: &str) -> anyhow::Result<Option<String>> {
+        match part {
+            Part::Part1 => flash_counts::<10, 10>(input, 100).map(|counts| {
+                Some(
+                    counts
+                        .iter()
+                        .enumerate()
+                        .map(|(step, count)| format!("step {}: {} flashes", step + 1, count))
+                        .join("\n"),
+                )
+            }),
+            Part::Part2 => {
+                let steps = part2(input)?;
+
+                animate::<10, 10>(input, steps).map(|frames| {
+                    Some(
+                        frames
+                            .iter()
+                            .enumerate()
+                            .map(|(step, frame)| format!("step {}:\n{}", step, frame))
+                            .join("\n\n"),
+                    )
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use gridly::prelude::Grid;
+    use proptest::prelude::*;
+
+    const SAMPLE: &str = "\
+5483143223
+2745854711
+5264556173
+6141336146
+6357385478
+4167524645
+2176841721
+6882881134
+4846848554
+5283751526
+";
+
+    const SMALL_SAMPLE: &str = "\
+11111
+19991
+19191
+19991
+11111
+";
+
+    #[test]
+    fn test_sample() {
+        assert_eq!(part1(SAMPLE).unwrap(), 1656);
+        assert_eq!(part2(SAMPLE).unwrap(), 195);
+    }
+
+    #[test]
+    fn test_solve_both() {
+        assert_eq!(
+            solve_both(SAMPLE).unwrap(),
+            (part1(SAMPLE).unwrap(), part2(SAMPLE).unwrap())
+        );
+
+        // A grid that synchronizes on step 2, and then every 10 steps
+        let early = "8888888888\n".repeat(10);
+        assert_eq!(part2(&early).unwrap(), 2);
+        assert_eq!(
+            solve_both(&early).unwrap(),
+            (part1(&early).unwrap(), part2(&early).unwrap())
+        );
+        assert_eq!(part1(&early).unwrap(), 1000);
+    }
+
+    #[test]
+    fn test_flash_counts() {
+        let counts = flash_counts::<10, 10>(SAMPLE, 10).unwrap();
+
+        assert_eq!(counts[..2], [0, 35]);
+        assert_eq!(counts.iter().sum::<usize>(), 204);
+    }
+
+    #[test]
+    fn test_animate() {
+        let frames = animate::<5, 5>(SMALL_SAMPLE, 2).unwrap();
+
+        assert_eq!(
+            frames,
+            [
+                SMALL_SAMPLE.trim_end(),
+                "34543\n4***4\n5***5\n4***4\n34543",
+                "45654\n51115\n61116\n51115\n45654",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_threshold() {
+        let input = "11\n11\n";
+
+        assert_eq!(
+            flash_counts_with::<2, 2>(input, 5, 3, false).unwrap(),
+            [0, 4, 0, 0, 4]
+        );
+        assert_eq!(
+            flash_counts::<2, 2>(input, 9).unwrap(),
+            [0, 0, 0, 0, 0, 0, 0, 0, 4]
+        );
+        assert!(flash_counts_with::<2, 2>(input, 5, 0, false).is_err());
+    }
+
+    #[test]
+    fn test_small_grid() {
+        assert_eq!(total_flashes::<5, 5>(SMALL_SAMPLE, 1).unwrap(), 9);
+        assert_eq!(total_flashes::<5, 5>(SMALL_SAMPLE, 2).unwrap(), 9);
+
+        // Every octopus in a full grid of 9s flashes on the first step
+        let nines = "99999\n".repeat(5);
+        assert_eq!(first_synchronized_step::<5, 5>(&nines).unwrap(), 1);
+
+        assert!(total_flashes::<5, 5>(SAMPLE, 1).is_err());
+    }
+
+    #[test]
+    fn test_start_past_threshold() {
+        // Every octopus starts past the threshold, so every octopus flashes
+        // exactly once on the first step. After that they all start from 0
+        // together, and need 2 steps to reach the threshold again.
+        let input = "999\n999\n999\n";
+
+        assert_eq!(
+            flash_counts_with::<3, 3>(input, 5, 2, false).unwrap(),
+            [9, 0, 9, 0, 9]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn test_each_octopus_flashes_once(
+            rows in prop::collection::vec("[0-9]{10}", 10),
+            threshold in 1i64..=10,
+            steps in 1usize..20,
+        ) {
+            let input = rows.join("\n");
+            let mut grid = parse_grid::<10, 10>(&input, threshold, false).unwrap();
+
+            for _ in 0..steps {
+                let before = grid.grid;
+                let flash_count = grid.take_step();
+
+                prop_assert_eq!(flash_count, grid.flash_buffer.len());
+
+                for row in grid.grid.row_range() {
+                    for column in grid.grid.column_range() {
+                        let location = row + column;
+                        let after = *grid.grid.get(location).unwrap();
+
+                        if grid.flash_buffer.contains(&location) {
+                            prop_assert_eq!(after, 0);
+                        } else {
+                            // Each flashing neighbor added exactly 1 energy
+                            let flashed_neighbors = neighbors_with_diagonals(&grid.grid, location)
+                                .filter(|(neighbor, _)| grid.flash_buffer.contains(neighbor))
+                                .count() as i64;
+
+                            prop_assert_eq!(
+                                after,
+                                before.get(location).unwrap() + 1 + flashed_neighbors
+                            );
+                            prop_assert!(after < threshold);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_wrapping() {
+        // The 9 in the top left corner is only a neighbor of the 8 in the
+        // bottom right corner if the grid wraps
+        let input = "9000\n0000\n0000\n0008\n";
+
+        assert_eq!(
+            flash_counts_with::<4, 4>(input, 1, FLASH_THRESHOLD, false).unwrap(),
+            [1]
+        );
+        assert_eq!(
+            flash_counts_with::<4, 4>(input, 1, FLASH_THRESHOLD, true).unwrap(),
+            [2]
+        );
+
+        // On a grid with no edges, every octopus has 8 neighbors
+        assert_eq!(wrapping_neighbors::<4, 4>(Location::zero()).count(), 8);
+        assert_eq!(wrapping_neighbors::<2, 2>(Location::zero()).count(), 3);
+    }
+}
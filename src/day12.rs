@@ -1,17 +1,26 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
 
-use anyhow::Context;
+use anyhow::{anyhow, ensure, Context};
+use itertools::Itertools;
 use nom::{
-    bytes::complete::take_while,
-    character::complete::{char, multispace0, multispace1},
-    AsChar, IResult, Parser,
+    character::complete::{alpha1, char, multispace0, multispace1},
+    IResult, Parser,
 };
 use nom_supreme::{
     error::ErrorTree,
     final_parser::{final_parser, Location},
-    multi::parse_separated_terminated,
+    multi::collect_separated_terminated,
     ParserExt,
 };
+use rayon::prelude::*;
+
+use crate::{
+    library::{format_parse_error, Counter},
+    Part, Solver,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 enum CaveId<'a> {
@@ -31,7 +40,7 @@ impl<'a> CaveId<'a> {
         }
     }
 
-    fn small_name(&self) -> Option<&str> {
+    fn small_name(&self) -> Option<&'a str> {
         match *self {
             CaveId::Small(s) => Some(s),
             _ => None,
@@ -39,10 +48,18 @@ impl<'a> CaveId<'a> {
     }
 }
 
+impl fmt::Display for CaveId<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            CaveId::Start => f.write_str("start"),
+            CaveId::End => f.write_str("end"),
+            CaveId::Big(name) | CaveId::Small(name) => f.write_str(name),
+        }
+    }
+}
+
 fn parse_cave_id(input: &str) -> IResult<&str, CaveId<'_>, ErrorTree<&str>> {
-    take_while(|c: char| c.is_alpha())
-        .map(CaveId::from_str)
-        .parse(input)
+    alpha1.map(CaveId::from_str).context("cave").parse(input)
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -51,145 +68,714 @@ struct Link<'a> {
     tail: CaveId<'a>,
 }
 
+impl fmt::Display for Link<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.head, self.tail)
+    }
+}
+
 fn parse_link(input: &str) -> IResult<&str, Link<'_>, ErrorTree<&str>> {
     parse_cave_id
         .terminated(char('-'))
         .and(parse_cave_id)
         .map(|(head, tail)| Link { head, tail })
+        .context("link")
         .parse(input)
 }
 
 #[derive(Debug, Clone, Default)]
 struct CaveMap<'a> {
     links: HashMap<CaveId<'a>, HashSet<CaveId<'a>>>,
+
+    /// An index for each small cave, used to track visited caves in a bitset
+    small_caves: HashMap<&'a str, usize>,
+}
+
+impl<'a> CaveMap<'a> {
+    /// Build a map from a list of links, which may be followed in either
+    /// direction. Links from a cave to itself, and links that appear more than
+    /// once (in either direction), are rejected.
+    fn from_links(links: &[Link<'a>]) -> anyhow::Result<Self> {
+        let mut map = Self::default();
+
+        for (index, &link) in links.iter().enumerate() {
+            let Link { head, tail } = link;
+
+            ensure!(
+                head != tail,
+                "link {} ({}) connects a cave to itself",
+                index,
+                link
+            );
+
+            let new_head = map.links.entry(head).or_default().insert(tail);
+            let new_tail = map.links.entry(tail).or_default().insert(head);
+            ensure!(
+                new_head && new_tail,
+                "link {} ({}) appears more than once",
+                index,
+                link
+            );
+
+            map.add_cave(head);
+            map.add_cave(tail);
+        }
+
+        Ok(map)
+    }
+
+    fn add_cave(&mut self, cave: CaveId<'a>) {
+        if let Some(name) = cave.small_name() {
+            let index = self.small_caves.len();
+            self.small_caves.entry(name).or_insert(index);
+        }
+    }
+
+    /// Check that the end can be reached from the start
+    fn is_connected(&self) -> bool {
+        let mut seen = HashSet::from([CaveId::Start]);
+        let mut queue = vec![CaveId::Start];
+
+        while let Some(cave) = queue.pop() {
+            if cave == CaveId::End {
+                return true;
+            }
+
+            for &dest in self.links.get(&cave).into_iter().flatten() {
+                if seen.insert(dest) {
+                    queue.push(dest);
+                }
+            }
+        }
+
+        false
+    }
+
+    fn destinations(&self, cave: CaveId<'a>) -> &HashSet<CaveId<'a>> {
+        self.links
+            .get(&cave)
+            .unwrap_or_else(|| panic!("Unexpected uni-directional link to cave {:?}", cave))
+    }
 }
 
-fn parse_cave_map(input: &str) -> IResult<&str, CaveMap<'_>, ErrorTree<&str>> {
-    parse_separated_terminated(
-        parse_link,
-        multispace1,
-        multispace0.all_consuming(),
-        CaveMap::default,
-        |mut map, Link { head, tail }| {
-            map.links.entry(head).or_default().insert(tail);
-            map.links.entry(tail).or_default().insert(head);
-            map
-        },
-    )
-    .parse(input)
+fn parse_cave_map(input: &str) -> IResult<&str, Vec<Link<'_>>, ErrorTree<&str>> {
+    collect_separated_terminated(parse_link, multispace1, multispace0.all_consuming()).parse(input)
 }
 
-fn final_parse_cave_map(input: &str) -> Result<CaveMap<'_>, ErrorTree<Location>> {
+fn final_parse_cave_map(input: &str) -> Result<Vec<Link<'_>>, ErrorTree<Location>> {
     final_parser(parse_cave_map)(input)
 }
 
-struct SmallCaveChain<'a> {
-    id: &'a str,
-    prev: Option<&'a SmallCaveChain<'a>>,
+/// The maximum number of small caves that can be tracked in a `VisitedCaves`
+const MAX_SMALL_CAVES: usize = 64;
+
+fn parse_map(input: &str) -> anyhow::Result<CaveMap<'_>> {
+    let links = final_parse_cave_map(input)
+        .map_err(|err| anyhow!(format_parse_error(input, &err)))
+        .context("parse error")?;
+    let map = CaveMap::from_links(&links)?;
+
+    ensure!(
+        map.links.contains_key(&CaveId::Start),
+        "the map has no start cave"
+    );
+    ensure!(
+        map.links.contains_key(&CaveId::End),
+        "the map has no end cave"
+    );
+    ensure!(map.is_connected(), "there's no path from start to end");
+
+    ensure!(
+        map.small_caves.len() <= MAX_SMALL_CAVES,
+        "the map has {} small caves, but at most {} are supported",
+        map.small_caves.len(),
+        MAX_SMALL_CAVES
+    );
+
+    Ok(map)
 }
 
-impl SmallCaveChain<'_> {
-    fn contains(&self, name: &str) -> bool {
-        self.id == name
-            || match self.prev {
-                Some(prev) => prev.contains(name),
-                None => false,
-            }
+/// The set of small caves visited so far on a route, as a bitset of their
+/// indexes in the cave map
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+struct VisitedCaves(u64);
+
+impl VisitedCaves {
+    fn contains(self, index: usize) -> bool {
+        self.0 & (1 << index) != 0
+    }
+
+    fn with(self, index: usize) -> Self {
+        Self(self.0 | (1 << index))
     }
 }
 
-fn count_routes_from(
-    map: &CaveMap,
-    start: CaveId,
-    small_caves: Option<&SmallCaveChain<'_>>,
+/// The small cave that a route has visited more than once, if any
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Revisit {
+    /// The index of the small cave
+    index: usize,
+
+    /// The number of times the route has visited it
+    visits: usize,
+}
+
+/// A partial route on the traversal stack. Two partial routes with the same
+/// frame have the same number of ways to reach the end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Frame<'a> {
+    cave: CaveId<'a>,
+    visited: VisitedCaves,
+    revisit: Option<Revisit>,
+
+    /// The number of times that a single small cave may be visited
+    max_visits: usize,
+}
+
+impl<'a> Frame<'a> {
+    /// Visit a small cave, or return None if this route can't visit it again.
+    /// Only one small cave can be visited more than once.
+    fn visit_small(self, cave: CaveId<'a>, index: usize) -> Option<Self> {
+        let revisit = match (self.visited.contains(index), self.revisit) {
+            (false, revisit) => revisit,
+            (true, None) => Some(Revisit { index, visits: 2 }),
+            (true, Some(revisit)) if revisit.index == index => Some(Revisit {
+                index,
+                visits: revisit.visits + 1,
+            }),
+            (true, Some(_)) => return None,
+        };
+
+        match revisit {
+            Some(revisit) if revisit.visits > self.max_visits => None,
+            revisit => Some(Frame {
+                cave,
+                visited: self.visited.with(index),
+                revisit,
+                ..self
+            }),
+        }
+    }
+
+    /// The frames for each of the caves that can be visited next from this
+    /// one. Start is never revisited.
+    fn next_frames(self, map: &'a CaveMap<'a>) -> impl Iterator<Item = Frame<'a>> + 'a {
+        map.destinations(self.cave)
+            .iter()
+            .filter_map(move |&dest| match dest {
+                CaveId::Start => None,
+                CaveId::Small(name) => self.visit_small(dest, map.small_caves[name]),
+                _ => Some(Frame { cave: dest, ..self }),
+            })
+    }
+}
+
+fn start_frame<'a>(max_visits: usize) -> Frame<'a> {
+    Frame {
+        cave: CaveId::Start,
+        visited: VisitedCaves::default(),
+        revisit: None,
+        max_visits,
+    }
+}
+
+/// Count the routes from a partial route to the end. The caves are traversed
+/// with an explicit stack, so large maps can't overflow the call stack, and
+/// the count for every frame is cached, so partial routes that reach the same
+/// state (the same cave, with the same small caves visited) are only counted
+/// once. This makes dense maps, where many routes pass through the same
+/// states, dramatically faster.
+fn count_routes_from_frame<'a>(
+    map: &'a CaveMap<'a>,
+    frame: Frame<'a>,
+    cache: &mut HashMap<Frame<'a>, usize>,
 ) -> usize {
-    if start == CaveId::End {
-        return 1;
+    // Each frame is pushed twice: once to push its next frames, and then
+    // again, after all of those have been counted, to add up their counts.
+    let mut stack = vec![(frame, false)];
+
+    while let Some((current, counted_next)) = stack.pop() {
+        if cache.contains_key(&current) {
+            continue;
+        }
+
+        if current.cave == CaveId::End {
+            cache.insert(current, 1);
+        } else if counted_next {
+            let count = current.next_frames(map).map(|next| cache[&next]).sum();
+            cache.insert(current, count);
+        } else {
+            stack.push((current, true));
+            stack.extend(
+                current
+                    .next_frames(map)
+                    .filter(|next| !cache.contains_key(next))
+                    .map(|next| (next, false)),
+            );
+        }
     }
 
-    let destinations = map
-        .links
-        .get(&start)
-        .unwrap_or_else(|| panic!("Unexpected uni-directional link to cave {:?}", start));
+    cache[&frame]
+}
 
-    destinations
-        .iter()
-        .filter(|&dest| match (*dest, small_caves) {
-            (CaveId::Small(name), Some(small_caves)) => !small_caves.contains(name),
-            (CaveId::Start, _) => false,
-            _ => true,
-        })
-        .map(|&dest| match dest.small_name() {
-            None => count_routes_from(map, dest, small_caves),
-            Some(name) => {
-                let small_caves = SmallCaveChain {
-                    id: name,
-                    prev: small_caves,
-                };
-                count_routes_from(map, dest, Some(&small_caves))
-            }
-        })
+/// Count the routes from the start to the end that visit small caves at most
+/// once, except that a single small cave may be visited up to `max_visits`
+/// times. The branches leaving the start are counted in parallel.
+fn count_routes(map: &CaveMap, max_visits: usize) -> usize {
+    let first_hops: Vec<Frame> = start_frame(max_visits).next_frames(map).collect();
+
+    first_hops
+        .into_par_iter()
+        .map(|frame| count_routes_from_frame(map, frame, &mut HashMap::new()))
         .sum()
 }
 
+/// List every route from the start to the end, following the same rules as
+/// `count_routes`. Every route is stored in full, so this is only suitable for
+/// small maps, like the examples; the number of routes grows very quickly.
+fn list_routes<'a>(map: &'a CaveMap<'a>, max_visits: usize) -> Vec<Vec<CaveId<'a>>> {
+    let mut stack = vec![(start_frame(max_visits), vec![CaveId::Start])];
+    let mut routes = Vec::new();
+
+    while let Some((frame, route)) = stack.pop() {
+        match frame.cave {
+            CaveId::End => routes.push(route),
+            _ => stack.extend(frame.next_frames(map).map(|next| {
+                let mut route = route.clone();
+                route.push(next.cave);
+                (next, route)
+            })),
+        }
+    }
+
+    routes
+}
+
+/// Every route through the caves that visits small caves at most once. This
+/// is only suitable for small inputs; see `list_routes`.
+pub fn routes(input: &str) -> anyhow::Result<Vec<String>> {
+    let map = parse_map(input)?;
+
+    Ok(list_routes(&map, 1)
+        .iter()
+        .map(|route| route.iter().join(","))
+        .collect())
+}
+
+/// Count the routes through the caves that visit small caves at most once by
+/// their length, measured as the number of links each route follows
+pub fn route_lengths(input: &str) -> anyhow::Result<Counter<usize>> {
+    let map = parse_map(input)?;
+    let mut stack = vec![(start_frame(1), 0)];
+    let mut lengths = Counter::new();
+
+    while let Some((frame, length)) = stack.pop() {
+        match frame.cave {
+            CaveId::End => lengths.add_one(length),
+            _ => stack.extend(frame.next_frames(&map).map(|next| (next, length + 1))),
+        }
+    }
+
+    Ok(lengths)
+}
+
 pub fn part1(input: &str) -> anyhow::Result<usize> {
-    let map = final_parse_cave_map(input).context("parse error")?;
-    Ok(count_routes_from(&map, CaveId::Start, None))
+    route_lengths(input).map(|lengths| lengths.total())
 }
 
-fn count_routes_from_visit_twice(
-    map: &CaveMap,
-    start: CaveId,
-    small_caves: Option<&SmallCaveChain<'_>>,
-    any_doubled: bool,
-) -> usize {
-    if start == CaveId::End {
-        return 1;
+/// Count the routes through the caves where a single small cave may be
+/// visited up to `max_visits` times, and every other small cave at most once
+pub fn solve(input: &str, max_visits: usize) -> anyhow::Result<usize> {
+    ensure!(
+        max_visits > 0,
+        "small caves must be visitable at least once"
+    );
+
+    parse_map(input).map(|map| count_routes(&map, max_visits))
+}
+
+pub fn part2(input: &str) -> anyhow::Result<usize> {
+    solve(input, 2)
+}
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
     }
 
-    let destinations = map
-        .links
-        .get(&start)
-        .unwrap_or_else(|| panic!("Unexpected uni-directional link to cave {:?}", start));
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
 
-    destinations
-        .iter()
-        .filter(|&dest| match (*dest, small_caves, any_doubled) {
-            // Only visit the start node once
-            (CaveId::Start, ..) => false,
-
-            // If we've visited any small cave twice, visited small caves are now off limits
-            (CaveId::Small(name), Some(small_caves), true) => !small_caves.contains(name),
-
-            // All other nodes can freely be revisited
-            _ => true,
-        })
-        .map(|&dest| match dest.small_name() {
-            None => count_routes_from_visit_twice(map, dest, small_caves, any_doubled),
-            Some(name) => {
-                let any_doubled = any_doubled
-                    || match small_caves {
-                        Some(caves) => caves.contains(name),
-                        None => false,
+    fn details(&self, part: Part, input: &str) -> anyhow::Result<Option<String>> {
+        match part {
+            Part::Part1 => routes(input).map(|routes| Some(routes.iter().sorted().join("\n"))),
+            Part::Part2 => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TINY_SAMPLE: &str = "\
+start-A
+start-b
+A-c
+A-b
+b-d
+A-end
+b-end
+";
+
+    const MEDIUM_SAMPLE: &str = "\
+dc-end
+HN-start
+start-kj
+dc-start
+dc-HN
+LN-dc
+HN-end
+kj-sa
+kj-HN
+kj-dc
+";
+
+    const LARGE_SAMPLE: &str = "\
+fs-end
+he-DX
+fs-he
+start-DX
+pj-DX
+end-zg
+zg-sl
+zg-pj
+pj-he
+RW-he
+fs-DX
+pj-RW
+zg-RW
+start-pj
+he-WI
+zg-he
+pj-fs
+start-RW
+";
+
+    const SAMPLES: [&str; 3] = [TINY_SAMPLE, MEDIUM_SAMPLE, LARGE_SAMPLE];
+
+    /// Count the routes from a partial route to the end by following every one
+    /// of them
+    fn count_without_cache<'a>(map: &'a CaveMap<'a>, frame: Frame<'a>) -> usize {
+        let mut stack = vec![frame];
+        let mut count = 0;
+
+        while let Some(frame) = stack.pop() {
+            match frame.cave {
+                CaveId::End => count += 1,
+                _ => stack.extend(frame.next_frames(map)),
+            }
+        }
+
+        count
+    }
+
+    /// Count routes by tracking how many times each small cave has been
+    /// visited, allowing one small cave up to `max_visits` visits
+    fn brute_force<'a>(
+        map: &CaveMap<'a>,
+        cave: CaveId<'a>,
+        visits: &mut HashMap<&'a str, usize>,
+        max_visits: usize,
+    ) -> usize {
+        if cave == CaveId::End {
+            return 1;
+        }
+
+        let mut count = 0;
+
+        for &dest in map.destinations(cave) {
+            match dest {
+                CaveId::Start => {}
+                CaveId::Small(name) => {
+                    *visits.entry(name).or_default() += 1;
+
+                    let revisited = visits.values().filter(|&&count| count > 1).count();
+                    if revisited <= 1 && visits[name] <= max_visits {
+                        count += brute_force(map, dest, visits, max_visits);
+                    }
+
+                    *visits.get_mut(name).unwrap() -= 1;
+                }
+                _ => count += brute_force(map, dest, visits, max_visits),
+            }
+        }
+
+        count
+    }
+
+    // The original recursive implementation, which tracks the visited small
+    // caves in a linked list on the call stack
+
+    struct SmallCaveChain<'a> {
+        id: &'a str,
+        prev: Option<&'a SmallCaveChain<'a>>,
+    }
+
+    impl SmallCaveChain<'_> {
+        fn contains(&self, name: &str) -> bool {
+            self.id == name
+                || match self.prev {
+                    Some(prev) => prev.contains(name),
+                    None => false,
+                }
+        }
+    }
+
+    fn count_routes_from(
+        map: &CaveMap,
+        start: CaveId,
+        small_caves: Option<&SmallCaveChain<'_>>,
+    ) -> usize {
+        if start == CaveId::End {
+            return 1;
+        }
+
+        let destinations = map
+            .links
+            .get(&start)
+            .unwrap_or_else(|| panic!("Unexpected uni-directional link to cave {:?}", start));
+
+        destinations
+            .iter()
+            .filter(|&dest| match (*dest, small_caves) {
+                (CaveId::Small(name), Some(small_caves)) => !small_caves.contains(name),
+                (CaveId::Start, _) => false,
+                _ => true,
+            })
+            .map(|&dest| match dest.small_name() {
+                None => count_routes_from(map, dest, small_caves),
+                Some(name) => {
+                    let small_caves = SmallCaveChain {
+                        id: name,
+                        prev: small_caves,
                     };
+                    count_routes_from(map, dest, Some(&small_caves))
+                }
+            })
+            .sum()
+    }
+
+    fn count_routes_from_visit_twice(
+        map: &CaveMap,
+        start: CaveId,
+        small_caves: Option<&SmallCaveChain<'_>>,
+        any_doubled: bool,
+    ) -> usize {
+        if start == CaveId::End {
+            return 1;
+        }
 
-                let small_caves = SmallCaveChain {
-                    id: name,
-                    prev: small_caves,
-                };
+        let destinations = map
+            .links
+            .get(&start)
+            .unwrap_or_else(|| panic!("Unexpected uni-directional link to cave {:?}", start));
+
+        destinations
+            .iter()
+            .filter(|&dest| match (*dest, small_caves, any_doubled) {
+                // Only visit the start node once
+                (CaveId::Start, ..) => false,
+
+                // If we've visited any small cave twice, visited small caves are now off limits
+                (CaveId::Small(name), Some(small_caves), true) => !small_caves.contains(name),
+
+                // All other nodes can freely be revisited
+                _ => true,
+            })
+            .map(|&dest| match dest.small_name() {
+                None => count_routes_from_visit_twice(map, dest, small_caves, any_doubled),
+                Some(name) => {
+                    let any_doubled = any_doubled
+                        || match small_caves {
+                            Some(caves) => caves.contains(name),
+                            None => false,
+                        };
+
+                    let small_caves = SmallCaveChain {
+                        id: name,
+                        prev: small_caves,
+                    };
 
-                count_routes_from_visit_twice(map, dest, Some(&small_caves), any_doubled)
+                    count_routes_from_visit_twice(map, dest, Some(&small_caves), any_doubled)
+                }
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_samples() {
+        assert_eq!(part1(TINY_SAMPLE).unwrap(), 10);
+        assert_eq!(part1(MEDIUM_SAMPLE).unwrap(), 19);
+        assert_eq!(part1(LARGE_SAMPLE).unwrap(), 226);
+
+        assert_eq!(part2(TINY_SAMPLE).unwrap(), 36);
+        assert_eq!(part2(MEDIUM_SAMPLE).unwrap(), 103);
+        assert_eq!(part2(LARGE_SAMPLE).unwrap(), 3509);
+    }
+
+    #[test]
+    fn test_iterative_matches_recursive() {
+        for input in SAMPLES {
+            let map = parse_map(input).unwrap();
+
+            assert_eq!(
+                count_routes(&map, 1),
+                count_routes_from(&map, CaveId::Start, None)
+            );
+            assert_eq!(
+                count_routes(&map, 2),
+                count_routes_from_visit_twice(&map, CaveId::Start, None, false)
+            );
+        }
+    }
+
+    #[test]
+    fn test_routes() {
+        let routes: Vec<String> = routes(TINY_SAMPLE).unwrap().into_iter().sorted().collect();
+
+        assert_eq!(
+            routes,
+            [
+                "start,A,b,A,c,A,end",
+                "start,A,b,A,end",
+                "start,A,b,end",
+                "start,A,c,A,b,A,end",
+                "start,A,c,A,b,end",
+                "start,A,c,A,end",
+                "start,A,end",
+                "start,b,A,c,A,end",
+                "start,b,A,end",
+                "start,b,end",
+            ]
+        );
+
+        for input in SAMPLES {
+            let map = parse_map(input).unwrap();
+            assert_eq!(list_routes(&map, 2).len(), count_routes(&map, 2));
+        }
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        for input in SAMPLES {
+            let map = parse_map(input).unwrap();
+
+            for max_visits in [1, 2, 3] {
+                assert_eq!(
+                    count_routes(&map, max_visits),
+                    count_without_cache(&map, start_frame(max_visits))
+                );
             }
-        })
-        .sum()
-}
+        }
+    }
 
-pub fn part2(input: &str) -> anyhow::Result<usize> {
-    let map = final_parse_cave_map(input).context("parse error")?;
-    Ok(count_routes_from_visit_twice(
-        &map,
-        CaveId::Start,
-        None,
-        false,
-    ))
+    #[test]
+    fn test_max_visits() {
+        for input in SAMPLES {
+            let map = parse_map(input).unwrap();
+
+            for max_visits in 1..=3 {
+                assert_eq!(
+                    solve(input, max_visits).unwrap(),
+                    brute_force(&map, CaveId::Start, &mut HashMap::new(), max_visits),
+                    "{} visits",
+                    max_visits
+                );
+            }
+        }
+
+        assert_eq!(solve(TINY_SAMPLE, 1).unwrap(), part1(TINY_SAMPLE).unwrap());
+        assert!(solve(TINY_SAMPLE, 0).is_err());
+    }
+
+    #[test]
+    fn test_cached_matches_uncached() {
+        for input in SAMPLES {
+            let map = parse_map(input).unwrap();
+
+            for max_visits in 1..=3 {
+                let start = start_frame(max_visits);
+
+                assert_eq!(
+                    count_routes_from_frame(&map, start, &mut HashMap::new()),
+                    count_without_cache(&map, start)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_dense_map() {
+        // Two big caves connected to every small cave, so there are over 100
+        // million routes but only a few thousand distinct states. Counting
+        // every route one at a time takes minutes in a debug build; with the
+        // cache it takes about a tenth of a second.
+        let small_caves = ["a", "b", "c", "d", "e", "f", "g"];
+        let input = ["start-A", "start-B", "A-end", "B-end"]
+            .into_iter()
+            .map(String::from)
+            .chain(
+                small_caves
+                    .iter()
+                    .flat_map(|cave| [format!("A-{}", cave), format!("B-{}", cave)]),
+            )
+            .join("\n");
+
+        assert_eq!(part2(&input).unwrap(), 106_894_118);
+    }
+
+    #[test]
+    fn test_disconnected() {
+        let err = part1("start-A\nA-b\n").unwrap_err();
+        assert_eq!(err.to_string(), "the map has no end cave");
+
+        let err = part1("A-b\nb-end\n").unwrap_err();
+        assert_eq!(err.to_string(), "the map has no start cave");
+
+        let err = part2("start-A\nA-b\nc-end\nc-D\n").unwrap_err();
+        assert_eq!(err.to_string(), "there's no path from start to end");
+    }
+
+    #[test]
+    fn test_route_lengths() {
+        let lengths = route_lengths(TINY_SAMPLE).unwrap();
+        let lengths: Vec<(usize, usize)> = lengths
+            .iter_counts()
+            .map(|(&length, count)| (length, count))
+            .sorted()
+            .collect();
+
+        assert_eq!(lengths, [(2, 2), (3, 2), (4, 2), (5, 2), (6, 2)]);
+    }
+
+    #[test]
+    fn test_invalid_links() {
+        let err = part1("start-A\nA-a\na-a\nA-end\n").unwrap_err();
+        assert_eq!(err.to_string(), "link 2 (a-a) connects a cave to itself");
+
+        let err = part1("start-A\nA-b\nb-A\nA-end\n").unwrap_err();
+        assert_eq!(err.to_string(), "link 2 (b-A) appears more than once");
+
+        let err = part1("start-A\nA-\nA-end\n").unwrap_err();
+        assert_eq!(err.to_string(), "parse error");
+        assert!(
+            format!("{:#}", err).contains("expected an ascii letter at line 2, column 3"),
+            "{:#}",
+            err
+        );
+    }
 }
@@ -1,11 +1,13 @@
-use std::{cmp::max, collections::HashSet};
+use std::{collections::HashSet, fmt};
 
-use anyhow::Context;
-use joinery::JoinableIterator;
+use anyhow::{anyhow, ensure, Context};
+use gridly::prelude::{Column, GridBounds, Row, Vector};
+use gridly_grids::{SparseGrid, VecGrid};
+use itertools::Itertools;
 use nom::{
     branch::alt,
-    character::complete::{char, digit1, line_ending, multispace0},
-    sequence::pair,
+    character::complete::{char, digit1, line_ending, multispace0, space0},
+    multi::many1_count,
     IResult, Parser,
 };
 use nom_supreme::{
@@ -16,13 +18,32 @@ use nom_supreme::{
     ParserExt,
 };
 
+use crate::{
+    grid::{render_occupied, trim_occupied},
+    library::format_parse_error,
+    Part, Solver,
+};
+
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 struct Location {
     x: i32,
     y: i32,
 }
 
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.x, self.y)
+    }
+}
+
 impl Location {
+    fn coordinate(self, axis: Axis) -> i32 {
+        match axis {
+            Axis::X => self.x,
+            Axis::Y => self.y,
+        }
+    }
+
     fn edit_axis(self, axis: Axis, body: impl Fn(i32) -> i32) -> Location {
         match axis {
             Axis::X => Location {
@@ -50,13 +71,21 @@ fn parse_location(input: &str) -> IResult<&str, Location, ErrorTree<&str>> {
         .parse(input)
 }
 
+/// Parse a line containing only whitespace, including its line ending
+fn parse_blank_line(input: &str) -> IResult<&str, (), ErrorTree<&str>> {
+    space0.terminated(line_ending).value(()).parse(input)
+}
+
+/// Parse the dots, one per line, up to and including the blank lines
+/// separating them from the fold instructions. There can be any number of
+/// blank lines, so long as there's at least one.
 fn parse_location_set<T: Extend<Location> + Default>(
     input: &str,
 ) -> IResult<&str, T, ErrorTree<&str>> {
     collect_separated_terminated(
-        parse_location.context("location"),
+        parse_location.terminated(space0).context("location"),
         line_ending,
-        pair(line_ending, line_ending),
+        line_ending.terminated(many1_count(parse_blank_line)),
     )
     .parse(input)
 }
@@ -73,6 +102,17 @@ struct FoldInstruction {
     edge: i32,
 }
 
+impl fmt::Display for FoldInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let axis = match self.axis {
+            Axis::X => 'x',
+            Axis::Y => 'y',
+        };
+
+        write!(f, "{}={}", axis, self.edge)
+    }
+}
+
 fn parse_axis(input: &str) -> IResult<&str, Axis, ErrorTree<&str>> {
     alt((char('x').value(Axis::X), char('y').value(Axis::Y))).parse(input)
 }
@@ -92,7 +132,9 @@ fn parse_fold_instruction(input: &str) -> IResult<&str, FoldInstruction, ErrorTr
 
 fn parse_fold_list(input: &str) -> IResult<&str, Vec<FoldInstruction>, ErrorTree<&str>> {
     collect_separated_terminated(
-        parse_fold_instruction.context("fold instruction"),
+        parse_fold_instruction
+            .terminated(space0)
+            .context("fold instruction"),
         line_ending,
         multispace0.all_consuming(),
     )
@@ -105,12 +147,45 @@ struct Page {
 }
 
 impl Page {
-    fn apply_fold(&mut self, fold: FoldInstruction) {
+    /// Fold the page, moving the dots past the fold line onto the part of the
+    /// page before it. Folding along a line past all of the dots doesn't change
+    /// anything. It's an error for a dot to be on the fold line, or so far
+    /// past it that it would be folded off the edge of the page; the page is
+    /// unchanged if the fold fails. Returns the number of dots that were
+    /// folded onto another dot, and so merged into it.
+    fn apply_fold(&mut self, fold: FoldInstruction) -> anyhow::Result<usize> {
+        for &dot in &self.dots {
+            let value = dot.coordinate(fold.axis);
+
+            ensure!(value != fold.edge, "the dot at {} is on the fold line", dot);
+            ensure!(
+                value - fold.edge <= fold.edge,
+                "the dot at {} would be folded off the page",
+                dot
+            );
+        }
+
+        let before = self.dots.len();
+
         self.dots = self
             .dots
             .drain()
             .map(move |loc| loc.edit_axis(fold.axis, |value| fold.edge - (value - fold.edge).abs()))
             .collect();
+
+        Ok(before - self.dots.len())
+    }
+
+    /// Get a sparse grid of the dots on the page, with `x` as the column and
+    /// `y` as the row
+    fn to_sparse_grid(&self) -> SparseGrid<bool> {
+        let mut grid = SparseGrid::new_default(Vector::zero(), false);
+
+        self.dots.iter().for_each(|dot| {
+            grid.insert(Row(dot.y as isize) + Column(dot.x as isize), true);
+        });
+
+        grid
     }
 }
 
@@ -133,39 +208,440 @@ fn final_parse_problem(
     final_parser(parse_problem)(input)
 }
 
+fn parse_input(input: &str) -> anyhow::Result<(Page, Vec<FoldInstruction>)> {
+    final_parse_problem(input)
+        .map_err(|err| anyhow!(format_parse_error(input, &err)))
+        .context("parse error")
+}
+
+/// Apply a fold, with context for errors
+fn apply_fold(page: &mut Page, index: usize, fold: FoldInstruction) -> anyhow::Result<usize> {
+    page.apply_fold(fold)
+        .with_context(|| format!("failed to apply fold {} (along {})", index, fold))
+}
+
+/// The result of a single fold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FoldResult {
+    /// The number of dots visible after the fold
+    pub dots: usize,
+
+    /// The number of dots that landed on another dot, and so merged into it
+    pub collisions: usize,
+}
+
+/// Apply each fold in turn, returning the result of each
+pub fn fold_results(input: &str) -> anyhow::Result<Vec<FoldResult>> {
+    lazy_fold_results(input)?.collect()
+}
+
+/// Lazily apply each fold in turn, so that callers only interested in the
+/// first few folds aren't affected by errors in later ones
+fn lazy_fold_results(
+    input: &str,
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<FoldResult>>> {
+    let (mut page, instructions) = parse_input(input)?;
+
+    Ok(instructions
+        .into_iter()
+        .enumerate()
+        .map(move |(index, instruction)| {
+            let collisions = apply_fold(&mut page, index, instruction)?;
+
+            Ok(FoldResult {
+                dots: page.dots.len(),
+                collisions,
+            })
+        }))
+}
+
 pub fn part1(input: &str) -> anyhow::Result<usize> {
-    let (mut page, instructions) = final_parse_problem(input).context("parse error")?;
-    let first = *instructions.first().context("no instructions in list")?;
-    page.apply_fold(first);
-    Ok(page.dots.len())
+    lazy_fold_results(input)?
+        .next()
+        .context("no instructions in list")?
+        .map(|result| result.dots)
 }
 
-pub fn part2(input: &str) -> anyhow::Result<String> {
-    let (mut page, instructions) = final_parse_problem(input).context("parse error")?;
-
-    instructions
-        .iter()
-        .for_each(|&instruction| page.apply_fold(instruction));
-
-    let max_coords = page
-        .dots
-        .iter()
-        .fold(Location::default(), |corner, &dot| Location {
-            x: max(corner.x, dot.x),
-            y: max(corner.y, dot.y),
-        });
+/// Apply every fold, returning the dots on the folded page as a sparse grid
+pub fn folded_page(input: &str) -> anyhow::Result<SparseGrid<bool>> {
+    let (mut page, instructions) = parse_input(input)?;
+
+    for (index, &instruction) in instructions.iter().enumerate() {
+        apply_fold(&mut page, index, instruction)?;
+    }
+
+    Ok(page.to_sparse_grid())
+}
+
+/// Trim a folded page to the bounding box of its dots, so that there's no
+/// blank border even if the dots don't reach the origin
+fn trim_page(page: &SparseGrid<bool>) -> anyhow::Result<VecGrid<bool>> {
+    trim_occupied(page).context("there are no dots on the page")
+}
+
+/// The width of each block letter on the folded page. Letters are separated
+/// by a blank column.
+const GLYPH_WIDTH: usize = 4;
+
+/// The height of each block letter on the folded page
+const GLYPH_HEIGHT: usize = 6;
 
-    Ok((0..=max_coords.y)
-        .map(|y| {
-            (0..=max_coords.x)
-                .map(move |x| Location { x, y })
-                .map(|loc| match page.dots.contains(&loc) {
-                    true => '█',
-                    false => ' ',
+/// The block letters that can appear on the folded page
+const GLYPHS: [(char, [&str; GLYPH_HEIGHT]); 16] = [
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// Read the block letters on a folded page
+fn ocr(grid: &VecGrid<bool>) -> anyhow::Result<String> {
+    let height = grid.num_rows().0 as usize;
+    let width = grid.num_columns().0 as usize;
+
+    ensure!(
+        height == GLYPH_HEIGHT,
+        "the page is {} rows tall, but letters are {} rows tall",
+        height,
+        GLYPH_HEIGHT
+    );
+    ensure!(
+        (width + 1).is_multiple_of(GLYPH_WIDTH + 1),
+        "the page is {} columns wide, which doesn't fit a whole number of letters",
+        width
+    );
+
+    let dot = |row: usize, column: usize| grid[Row(row as isize) + Column(column as isize)];
+
+    (0..(width + 1) / (GLYPH_WIDTH + 1))
+        .map(|index| {
+            let left = index * (GLYPH_WIDTH + 1);
+
+            let glyph: Vec<String> = (0..GLYPH_HEIGHT)
+                .map(|row| {
+                    (left..left + GLYPH_WIDTH)
+                        .map(|column| if dot(row, column) { '#' } else { '.' })
+                        .collect()
                 })
-                .join_concat()
+                .collect();
+
+            let spacer = left + GLYPH_WIDTH;
+            ensure!(
+                spacer == width || (0..GLYPH_HEIGHT).all(|row| !dot(row, spacer)),
+                "letter {} isn't followed by a blank column",
+                index
+            );
+
+            GLYPHS
+                .iter()
+                .find(|(_, rows)| *rows == glyph.as_slice())
+                .map(|&(letter, _)| letter)
+                .with_context(|| format!("unrecognized letter {}:\n{}", index, glyph.join("\n")))
         })
-        .join_with('\n')
-        // TODO: Find a way to get rid of this to_string
-        .to_string())
+        .collect()
+}
+
+/// The character used by default to render a dot
+pub const DOT: char = '█';
+
+/// The character used by default to render an empty spot on the page
+pub const BLANK: char = ' ';
+
+/// Render the folded page, using `dot` for each dot and `blank` for the
+/// spaces between them. The rendering is trimmed to the bounding box of the
+/// dots.
+pub fn render_page_with(page: &SparseGrid<bool>, dot: char, blank: char) -> String {
+    render_occupied(page, |&is_dot| match is_dot {
+        true => dot,
+        false => blank,
+    })
+}
+
+/// Render the folded page as block letters
+fn render_page(page: &SparseGrid<bool>) -> String {
+    render_page_with(page, DOT, BLANK)
+}
+
+pub fn part2(input: &str) -> anyhow::Result<String> {
+    let page = folded_page(input)?;
+    let grid = trim_page(&page)?;
+
+    ocr(&grid).with_context(|| format!("failed to read the folded page:\n{}", render_page(&page)))
+}
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
+
+    fn details(&self, part: Part, input: &str) -> anyhow::Result<Option<String>> {
+        match part {
+            Part::Part1 => fold_results(input).map(|results| {
+                Some(
+                    results
+                        .iter()
+                        .enumerate()
+                        .map(|(index, result)| {
+                            format!(
+                                "fold {}: {} dots, {} collisions",
+                                index + 1,
+                                result.dots,
+                                result.collisions
+                            )
+                        })
+                        .join("\n"),
+                )
+            }),
+            Part::Part2 => folded_page(input).map(|page| Some(render_page(&page))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use gridly::prelude::*;
+
+    const SAMPLE: &str = "\
+6,10
+0,14
+9,10
+0,3
+10,4
+4,11
+6,0
+6,12
+4,1
+0,13
+10,12
+3,4
+3,0
+8,4
+1,10
+2,14
+8,10
+9,0
+
+fold along y=7
+fold along x=5
+";
+
+    /// Apply every fold, returning the dots on the folded page as a grid
+    /// trimmed to the bounding box of the dots
+    fn folded_grid(input: &str) -> anyhow::Result<VecGrid<bool>> {
+        folded_page(input).and_then(|page| trim_page(&page))
+    }
+
+    /// The number of dots visible after each fold
+    fn dot_counts(input: &str) -> anyhow::Result<Vec<usize>> {
+        fold_results(input).map(|results| results.iter().map(|result| result.dots).collect())
+    }
+
+    /// Convert a grid of dots into rows of '#' and '.'
+    fn grid_rows(grid: &VecGrid<bool>) -> Vec<String> {
+        grid.rows()
+            .iter()
+            .map(|row| row.iter().map(|&dot| if dot { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    /// Build an input with a dot for each '#' in `art`, and a fold that
+    /// doesn't change anything
+    fn page_input(art: &[&str]) -> String {
+        let mut dots = art.iter().enumerate().flat_map(|(y, row)| {
+            row.char_indices()
+                .filter(|&(_, c)| c == '#')
+                .map(move |(x, _)| format!("{},{}", x, y))
+        });
+
+        format!("{}\n\nfold along y=100\n", dots.join("\n"))
+    }
+
+    #[test]
+    fn test_sample() {
+        assert_eq!(part1(SAMPLE).unwrap(), 17);
+
+        // The sample folds into a square, which isn't a letter
+        let err = part2(SAMPLE).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "failed to read the folded page:\n\
+            █████\n█   █\n█   █\n█   █\n█████: \
+            the page is 5 rows tall, but letters are 6 rows tall"
+        );
+    }
+
+    #[test]
+    fn test_ocr() {
+        let input = page_input(&[
+            "###..####.####.###.",
+            "#..#....#.#....#..#",
+            "#..#...#..###..###.",
+            "###...#...#....#..#",
+            "#....#....#....#..#",
+            "#....####.#....###.",
+        ]);
+
+        assert_eq!(part2(&input).unwrap(), "PZFB");
+    }
+
+    #[test]
+    fn test_unrecognized_letter() {
+        let input = page_input(&[
+            "#..#.####",
+            "#..#.#..#",
+            "####.#..#",
+            "#..#.#..#",
+            "#..#.#..#",
+            "#..#.####",
+        ]);
+
+        let err = ocr(&folded_grid(&input).unwrap()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unrecognized letter 1:\n####\n#..#\n#..#\n#..#\n#..#\n####"
+        );
+    }
+
+    #[test]
+    fn test_dot_counts() {
+        assert_eq!(dot_counts(SAMPLE).unwrap(), [17, 16]);
+    }
+
+    #[test]
+    fn test_collisions() {
+        // The first fold lands 2 dots on other dots, and the second fold
+        // doesn't land any
+        let input = "0,0\n4,0\n1,1\n3,1\n0,3\n\nfold along x=2\nfold along y=2\n";
+
+        assert_eq!(
+            fold_results(input).unwrap(),
+            [
+                FoldResult {
+                    dots: 3,
+                    collisions: 2
+                },
+                FoldResult {
+                    dots: 3,
+                    collisions: 0
+                },
+            ]
+        );
+
+        let collisions: Vec<usize> = fold_results(SAMPLE)
+            .unwrap()
+            .iter()
+            .map(|result| result.collisions)
+            .collect();
+
+        assert_eq!(collisions, [1, 1]);
+    }
+
+    #[test]
+    fn test_crlf() {
+        let input = SAMPLE.replace('\n', "\r\n");
+
+        assert_eq!(dot_counts(&input).unwrap(), [17, 16]);
+    }
+
+    #[test]
+    fn test_extra_whitespace() {
+        let input = "6,10 \n0,14\n9,10\t\n\n  \n\nfold along y=7  \r\nfold along x=5\n\n";
+
+        assert_eq!(dot_counts(input).unwrap(), [3, 3]);
+    }
+
+    #[test]
+    fn test_fold_past_edge() {
+        // Folding along a line past every dot doesn't change anything
+        let input = "0,0\n3,1\n\nfold along x=10\nfold along y=5\n";
+        assert_eq!(dot_counts(input).unwrap(), [2, 2]);
+        assert_eq!(
+            part2(input).unwrap_err().to_string(),
+            "failed to read the folded page:\n█   \n   █"
+        );
+
+        // Even if the fold line is so far away that twice its distance would
+        // overflow
+        let input = "0,0\n3,1\n\nfold along x=2000000000\n";
+        assert_eq!(dot_counts(input).unwrap(), [2]);
+
+        // A dot more than twice as far as the fold line would have a negative
+        // coordinate after the fold
+        let err = dot_counts("0,0\n9,1\n\nfold along x=4\n").unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "failed to apply fold 0 (along x=4): the dot at 9,1 would be folded off the page"
+        );
+
+        let err = dot_counts("0,0\n2,7\n\nfold along y=8\nfold along y=7\n").unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "failed to apply fold 1 (along y=7): the dot at 2,7 is on the fold line"
+        );
+
+        // Part 1 only needs the first fold, so a bad fold after it is fine
+        assert_eq!(
+            part1("0,0\n2,7\n\nfold along y=8\nfold along y=7\n").unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_folded_grid() {
+        let grid = folded_grid(SAMPLE).unwrap();
+
+        assert_eq!(grid.dimensions(), Rows(5) + Columns(5));
+        assert_eq!(
+            grid_rows(&grid),
+            ["#####", "#...#", "#...#", "#...#", "#####"]
+        );
+    }
+
+    #[test]
+    fn test_trimmed_grid() {
+        let input = page_input(&[".....", "......", "..#.#", "...#.", "....."]);
+        let grid = folded_grid(&input).unwrap();
+
+        assert_eq!(grid.dimensions(), Rows(2) + Columns(3));
+        assert_eq!(grid_rows(&grid), ["#.#", ".#."]);
+    }
+
+    #[test]
+    fn test_render_trimmed_page() {
+        let input = page_input(&[".....", "......", "..#.#", "...#.", "....."]);
+        let page = folded_page(&input).unwrap();
+
+        assert_eq!(render_page_with(&page, '#', '.'), "#.#\n.#.");
+    }
+
+    #[test]
+    fn test_render_characters() {
+        let page = folded_page(SAMPLE).unwrap();
+
+        assert_eq!(
+            render_page_with(&page, '#', '.'),
+            "#####\n#...#\n#...#\n#...#\n#####"
+        );
+        assert_eq!(render_page(&page), "█████\n█   █\n█   █\n█   █\n█████");
+    }
 }
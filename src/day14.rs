@@ -1,8 +1,9 @@
-use std::collections::HashMap;
+use std::{cmp::Reverse, collections::HashMap, hash::Hash, mem};
 
-use anyhow::{bail, Context};
+use anyhow::{anyhow, bail, Context};
 use itertools::{Itertools, MinMaxResult};
 use nom::{
+    bytes::complete::take_while,
     character::complete::{line_ending, multispace0, multispace1, satisfy},
     combinator::success,
     sequence::pair,
@@ -15,33 +16,72 @@ use nom_supreme::{
     tag::complete::tag,
     ParserExt,
 };
+use rayon::prelude::*;
 
-use crate::library::{Counter, IterExt};
+use crate::{
+    library::{format_parse_error, Counter},
+    Part, Solver,
+};
 
+/// An element, identified by its index in an `Elements` table
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 struct Chem {
-    name: char,
+    id: u16,
+}
+
+/// Table of all of the element names appearing in the puzzle, so that the
+/// pair bookkeeping can use small copyable ids instead of strings
+#[derive(Debug, Default, Clone)]
+struct Elements<'a> {
+    names: Vec<&'a str>,
+    ids: HashMap<&'a str, Chem>,
 }
 
-fn parse_chem(input: &str) -> IResult<&str, Chem, ErrorTree<&str>> {
+impl<'a> Elements<'a> {
+    /// Get the id for an element name, assigning a new one if necessary
+    fn intern(&mut self, name: &'a str) -> anyhow::Result<Chem> {
+        if let Some(&chem) = self.ids.get(name) {
+            return Ok(chem);
+        }
+
+        let chem = Chem {
+            id: self
+                .names
+                .len()
+                .try_into()
+                .context("too many distinct elements")?,
+        };
+
+        self.names.push(name);
+        self.ids.insert(name, chem);
+        Ok(chem)
+    }
+
+    fn name(&self, chem: Chem) -> &'a str {
+        self.names[chem.id as usize]
+    }
+}
+
+/// Parse an element name: an uppercase letter, followed by any number of
+/// lowercase letters, like `N` or `Fe`
+fn parse_chem(input: &str) -> IResult<&str, &str, ErrorTree<&str>> {
     satisfy(|c: char| c.is_ascii_uppercase())
-        .map(|name| Chem { name })
+        .precedes(take_while(|c: char| c.is_ascii_lowercase()))
+        .recognize()
         .parse(input)
 }
 
-fn parse_polymer(input: &str) -> IResult<&str, Polymer, ErrorTree<&str>> {
-    collect_separated_terminated(parse_chem, success(()), line_ending)
-        .map(|chems: Vec<Chem>| chems.into_iter().collect())
-        .parse(input)
+fn parse_polymer(input: &str) -> IResult<&str, Vec<&str>, ErrorTree<&str>> {
+    collect_separated_terminated(parse_chem, success(()), line_ending).parse(input)
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Rule {
-    pattern: (Chem, Chem),
-    insert: Chem,
+struct Rule<'a> {
+    pattern: (&'a str, &'a str),
+    insert: &'a str,
 }
 
-fn parse_rule(input: &str) -> IResult<&str, Rule, ErrorTree<&str>> {
+fn parse_rule(input: &str) -> IResult<&str, Rule<'_>, ErrorTree<&str>> {
     pair(parse_chem, parse_chem)
         .context("pattern")
         .terminated(tag(" -> "))
@@ -50,23 +90,47 @@ fn parse_rule(input: &str) -> IResult<&str, Rule, ErrorTree<&str>> {
         .parse(input)
 }
 
-fn parse_rule_list<T: Extend<Rule> + Default>(input: &str) -> IResult<&str, T, ErrorTree<&str>> {
+fn parse_rule_list(input: &str) -> IResult<&str, Vec<Rule<'_>>, ErrorTree<&str>> {
     collect_separated_terminated(parse_rule, line_ending, multispace0.all_consuming()).parse(input)
 }
 
+/// What to do with a pair that has no matching rule
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Strictness {
+    /// A pair with no matching rule is an error. The puzzle itself always has
+    /// a rule for every pair.
+    #[default]
+    Strict,
+
+    /// A pair with no matching rule is left unchanged. This is for inputs
+    /// with sparse rule sets.
+    Lenient,
+}
+
 #[derive(Debug, Default, Clone)]
-struct RuleSet {
+struct RuleSet<'a> {
     rules: HashMap<(Chem, Chem), Chem>,
+    elements: Elements<'a>,
+    strictness: Strictness,
 }
 
-impl Extend<Rule> for RuleSet {
-    fn extend<T: IntoIterator<Item = Rule>>(&mut self, iter: T) {
-        self.rules
-            .extend(iter.into_iter().map(|rule| (rule.pattern, rule.insert)))
+impl RuleSet<'_> {
+    /// Get the element inserted between `a` and `b`, or None if there's no
+    /// matching rule and the rule set is lenient
+    fn insertion(&self, a: Chem, b: Chem) -> anyhow::Result<Option<Chem>> {
+        match self.rules.get(&(a, b)) {
+            Some(&new) => Ok(Some(new)),
+            None if self.strictness == Strictness::Lenient => Ok(None),
+            None => bail!(
+                "no matching rule {}{}",
+                self.elements.name(a),
+                self.elements.name(b)
+            ),
+        }
     }
 }
 
-fn parse_problem(input: &str) -> IResult<&str, (Polymer, RuleSet), ErrorTree<&str>> {
+fn parse_problem(input: &str) -> IResult<&str, (Vec<&str>, Vec<Rule<'_>>), ErrorTree<&str>> {
     parse_polymer
         .context("template")
         .terminated(multispace1)
@@ -74,14 +138,44 @@ fn parse_problem(input: &str) -> IResult<&str, (Polymer, RuleSet), ErrorTree<&st
         .parse(input)
 }
 
-fn final_parse_problem(input: &str) -> Result<(Polymer, RuleSet), ErrorTree<Location>> {
+fn final_parse_problem(input: &str) -> Result<(Vec<&str>, Vec<Rule<'_>>), ErrorTree<Location>> {
     final_parser(parse_problem)(input)
 }
 
+/// Parse the puzzle input, assigning ids to all of the elements in the
+/// template and rules
+fn parse_input(input: &str, strictness: Strictness) -> anyhow::Result<(Polymer, RuleSet<'_>)> {
+    let (template, rule_list) = final_parse_problem(input)
+        .map_err(|err| anyhow!(format_parse_error(input, &err)))
+        .context("parse error")?;
+
+    let mut rules = RuleSet {
+        strictness,
+        ..RuleSet::default()
+    };
+
+    let polymer = template
+        .iter()
+        .map(|&name| rules.elements.intern(name))
+        .try_collect()?;
+
+    for rule in rule_list {
+        let pattern = (
+            rules.elements.intern(rule.pattern.0)?,
+            rules.elements.intern(rule.pattern.1)?,
+        );
+        let insert = rules.elements.intern(rule.insert)?;
+
+        rules.rules.insert(pattern, insert);
+    }
+
+    Ok((polymer, rules))
+}
+
 #[derive(Debug, Clone, Default)]
 struct Polymer {
-    pairs: Counter<(Chem, Chem)>,
-    counts: Counter<Chem>,
+    pairs: Counter<(Chem, Chem), u128>,
+    counts: Counter<Chem, u128>,
 }
 
 impl FromIterator<Chem> for Polymer {
@@ -107,46 +201,181 @@ impl FromIterator<Chem> for Polymer {
 }
 
 impl Polymer {
+    /// Ensure that the element counts can't overflow in the next `steps`
+    /// steps. Each step inserts at most one element into each pair, so the
+    /// number of pairs at most doubles every step.
+    fn ensure_countable(&self, steps: usize) -> anyhow::Result<()> {
+        let pairs = self.counts.total().saturating_sub(1);
+
+        if pairs == 0 {
+            return Ok(());
+        }
+
+        u32::try_from(steps)
+            .ok()
+            .and_then(|steps| 2u128.checked_pow(steps))
+            .and_then(|growth| pairs.checked_mul(growth))
+            .and_then(|pairs| pairs.checked_add(1))
+            .map(drop)
+            .context("the polymer would grow too long to count")
+    }
+
     fn apply_rules(self, rules: &RuleSet) -> anyhow::Result<Self> {
-        let pairs = self.pairs;
-        let mut counts = self.counts;
+        self.ensure_countable(1)?;
 
-        pairs
+        // Check for missing rules up front, in order by name, so that the
+        // error doesn't depend on how the pairs are split between threads
+        self.pairs
             .iter_counts()
-            .map(|(&(a, b), count)| {
-                rules
-                    .rules
-                    .get(&(a, b))
-                    .with_context(|| format!("no matching rule {:?}", (a, b)))
-                    .map(|&new| ((a, new, b), count))
-            })
-            .use_oks(move |insertions| {
-                let mut pairs = Counter::new();
+            .map(|(&pair, _)| pair)
+            .sorted_unstable_by_key(|&(a, b)| (rules.elements.name(a), rules.elements.name(b)))
+            .try_for_each(|(a, b)| rules.insertion(a, b).map(drop))?;
 
-                insertions.for_each(|((a, new, b), count)| {
-                    counts.add(new, count);
-                    pairs.add((a, new), count);
-                    pairs.add((new, b), count);
-                });
+        let (pairs, inserted) = self
+            .pairs
+            .par_iter_counts()
+            .fold(
+                || (Counter::new(), Counter::new()),
+                |(mut pairs, mut inserted), (&(a, b), count)| {
+                    // Every pair was checked above, so this is only None if
+                    // the rules are lenient
+                    match rules.insertion(a, b).ok().flatten() {
+                        Some(new) => {
+                            inserted.add(new, count);
+                            pairs.add((a, new), count);
+                            pairs.add((new, b), count);
+                        }
+                        None => pairs.add((a, b), count),
+                    }
 
-                Polymer { pairs, counts }
-            })
+                    (pairs, inserted)
+                },
+            )
+            .reduce(
+                || (Counter::new(), Counter::new()),
+                |(pairs1, inserted1), (pairs2, inserted2)| {
+                    (pairs1.merge(pairs2), inserted1.merge(inserted2))
+                },
+            );
+
+        Ok(Polymer {
+            pairs,
+            counts: self.counts.merge(inserted),
+        })
+    }
+
+    /// Apply the rules `count` times
+    fn apply_steps(self, rules: &RuleSet, count: usize) -> anyhow::Result<Self> {
+        (0..count).try_fold(self, |chem, step| {
+            chem.apply_rules(rules)
+                .with_context(|| format!("failure at step {}", step + 1))
+        })
     }
 }
 
-fn solve(input: &str, count: usize) -> anyhow::Result<usize> {
-    let (chem, rules) = final_parse_problem(input).context("parse error")?;
+/// Memoized expansion of individual pairs. The iterative `apply_rules` has
+/// to start over from the template for each step count; this instead reuses
+/// the expansions from earlier step counts, which is useful for answering
+/// several step counts for the same rules.
+struct Expander<'r, 'a> {
+    rules: &'r RuleSet<'a>,
+    cache: HashMap<((Chem, Chem), usize), Counter<Chem, u128>>,
+}
+
+impl<'r, 'a> Expander<'r, 'a> {
+    fn new(rules: &'r RuleSet<'a>) -> Self {
+        Self {
+            rules,
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Count the elements inserted between the elements of `pair` by
+    /// applying the rules `depth` times. The pair's own elements aren't
+    /// included.
+    fn expand(&mut self, pair: (Chem, Chem), depth: usize) -> anyhow::Result<Counter<Chem, u128>> {
+        if depth == 0 {
+            return Ok(Counter::new());
+        }
+
+        if let Some(counts) = self.cache.get(&(pair, depth)) {
+            return Ok(counts.clone());
+        }
+
+        let (a, b) = pair;
 
-    let final_chem = (0..count).try_fold(chem, |chem, step| {
-        chem.apply_rules(&rules)
-            .with_context(|| format!("failure at step {}", step + 1))
-    })?;
+        // If there's no rule for this pair, it never changes
+        let new = match self.rules.insertion(a, b)? {
+            Some(new) => new,
+            None => return Ok(Counter::new()),
+        };
+
+        let mut counts = self
+            .expand((a, new), depth - 1)?
+            .merge(self.expand((new, b), depth - 1)?);
+        counts.add_one(new);
 
-    let minmax = final_chem
+        self.cache.insert((pair, depth), counts.clone());
+        Ok(counts)
+    }
+
+    /// Count the elements in the polymer after applying the rules `depth`
+    /// times
+    fn element_counts(
+        &mut self,
+        polymer: &Polymer,
+        depth: usize,
+    ) -> anyhow::Result<Counter<Chem, u128>> {
+        polymer.ensure_countable(depth)?;
+
+        let mut counts = polymer.counts.clone();
+
+        for (&pair, count) in polymer.pairs.iter_counts() {
+            self.expand(pair, depth)?
+                .iter_counts()
+                .for_each(|(&chem, inserted)| counts.add(chem, inserted * count));
+        }
+
+        Ok(counts)
+    }
+}
+
+/// Apply the rules to the template `count` times, and return how many of
+/// each element are in the final polymer, by name
+fn element_counts(
+    input: &str,
+    count: usize,
+    strictness: Strictness,
+) -> anyhow::Result<Counter<&str, u128>> {
+    let (chem, rules) = parse_input(input, strictness)?;
+    let final_chem = chem.apply_steps(&rules, count)?;
+
+    Ok(final_chem
         .counts
-        .iter_counts()
-        .map(|(_, count)| count)
-        .minmax();
+        .into_iter()
+        .map(|(chem, count)| (rules.elements.name(chem), count))
+        .collect())
+}
+
+/// Apply the rules to the template `count` times, and return the length of
+/// the polymer after each step
+fn polymer_lengths(input: &str, count: usize, strictness: Strictness) -> anyhow::Result<Vec<u128>> {
+    let (mut chem, rules) = parse_input(input, strictness)?;
+
+    (0..count)
+        .map(|step| {
+            chem = mem::take(&mut chem)
+                .apply_rules(&rules)
+                .with_context(|| format!("failure at step {}", step + 1))?;
+
+            Ok(chem.counts.total())
+        })
+        .collect()
+}
+
+/// The difference between the most and least common element counts
+fn spread<T: Eq + Hash>(counts: &Counter<T, u128>) -> anyhow::Result<u128> {
+    let minmax = counts.iter_counts().map(|(_, count)| count).minmax();
 
     Ok(match minmax {
         MinMaxResult::NoElements => bail!("No chemicals!"),
@@ -155,10 +384,376 @@ fn solve(input: &str, count: usize) -> anyhow::Result<usize> {
     })
 }
 
-pub fn part1(input: &str) -> anyhow::Result<usize> {
-    solve(input, 10)
+/// An element name, and how many of it there are in a polymer
+type ElementCount<'a> = (&'a str, u128);
+
+/// Find the most and least common elements in the polymer after applying the
+/// rules `count` times. Ties are broken in favor of the alphabetically first
+/// element.
+fn extremes(
+    input: &str,
+    count: usize,
+    strictness: Strictness,
+) -> anyhow::Result<(ElementCount<'_>, ElementCount<'_>)> {
+    let counts = element_counts(input, count, strictness)?;
+
+    let most = counts
+        .iter_counts()
+        .map(|(&name, count)| (name, count))
+        .max_by_key(|&(name, count)| (count, Reverse(name)))
+        .context("No chemicals!")?;
+
+    let least = counts
+        .iter_counts()
+        .map(|(&name, count)| (name, count))
+        .min_by_key(|&(name, count)| (count, name))
+        .context("No chemicals!")?;
+
+    Ok((most, least))
+}
+
+/// Apply the rules to the template `count` times, and return the difference
+/// between the most and least common element counts
+pub fn solve(input: &str, count: usize, strictness: Strictness) -> anyhow::Result<u128> {
+    extremes(input, count, strictness).map(|((_, most), (_, least))| most - least)
+}
+
+/// Solve both parts at once, reusing the pair expansions from part 1 in
+/// part 2
+fn solve_both(input: &str) -> anyhow::Result<(u128, u128)> {
+    let (polymer, rules) = parse_input(input, Strictness::Strict)?;
+    let mut expander = Expander::new(&rules);
+
+    let part1 = expander
+        .element_counts(&polymer, 10)
+        .context("failed to expand 10 steps")?;
+    let part2 = expander
+        .element_counts(&polymer, 40)
+        .context("failed to expand 40 steps")?;
+
+    Ok((spread(&part1)?, spread(&part2)?))
+}
+
+/// The number of steps to apply for each part
+fn step_count(part: Part) -> usize {
+    match part {
+        Part::Part1 => 10,
+        Part::Part2 => 40,
+    }
 }
 
-pub fn part2(input: &str) -> anyhow::Result<usize> {
-    solve(input, 40)
+pub fn part1(input: &str) -> anyhow::Result<u128> {
+    solve(input, step_count(Part::Part1), Strictness::Strict)
+}
+
+pub fn part2(input: &str) -> anyhow::Result<u128> {
+    solve(input, step_count(Part::Part2), Strictness::Strict)
+}
+
+/// Describe the polymer after applying the rules `count` times: its most and
+/// least common elements, and its length after each step
+pub fn describe(input: &str, count: usize, strictness: Strictness) -> anyhow::Result<String> {
+    let ((most, most_count), (least, least_count)) = extremes(input, count, strictness)?;
+    let lengths = polymer_lengths(input, count, strictness)?;
+
+    Ok(format!(
+        "most common: {} ({})\nleast common: {} ({})\nlength after each step: {}",
+        most,
+        most_count,
+        least,
+        least_count,
+        lengths.iter().join(", ")
+    ))
+}
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
+
+    fn both(&self, input: &str) -> anyhow::Result<(String, String)> {
+        let (part1, part2) = solve_both(input)?;
+        Ok((part1.to_string(), part2.to_string()))
+    }
+
+    fn details(&self, part: Part, input: &str) -> anyhow::Result<Option<String>> {
+        describe(input, step_count(part), Strictness::Strict).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::library::IterExt;
+
+    const SAMPLE: &str = "\
+NNCB
+
+CH -> B
+HH -> N
+CB -> H
+NH -> C
+HB -> C
+HC -> B
+HN -> C
+NN -> C
+BH -> H
+NC -> B
+NB -> B
+BN -> B
+BB -> N
+BC -> B
+CC -> N
+CN -> C
+";
+
+    #[test]
+    fn test_sample() {
+        assert_eq!(part1(SAMPLE).unwrap(), 1588);
+        assert_eq!(part2(SAMPLE).unwrap(), 2188189693529);
+    }
+
+    #[test]
+    fn test_element_counts() {
+        let counts = element_counts(SAMPLE, 10, Strictness::Strict).unwrap();
+        let expected: Counter<&str, u128> = [("B", 1749), ("C", 298), ("H", 161), ("N", 865)]
+            .into_iter()
+            .collect();
+
+        assert_eq!(counts, expected);
+        assert_eq!(counts.total(), 3073);
+    }
+
+    #[test]
+    fn test_extremes() {
+        assert_eq!(
+            extremes(SAMPLE, 10, Strictness::Strict).unwrap(),
+            (("B", 1749), ("H", 161))
+        );
+    }
+
+    #[test]
+    fn test_extremes_tie() {
+        // With no steps, N and C are tied for most common, and B and H are
+        // tied for least common
+        let input = "NNCBCH\n\nNN -> C\n";
+
+        assert_eq!(
+            extremes(input, 0, Strictness::Strict).unwrap(),
+            (("C", 2), ("B", 1))
+        );
+    }
+
+    #[test]
+    fn test_expand_matches_apply_rules() {
+        let (polymer, rules) = parse_input(SAMPLE, Strictness::Strict).unwrap();
+        let mut expander = Expander::new(&rules);
+
+        for depth in [10, 40] {
+            let iterative = polymer.clone().apply_steps(&rules, depth).unwrap();
+            let memoized = expander.element_counts(&polymer, depth).unwrap();
+
+            assert_eq!(memoized, iterative.counts, "depth {}", depth);
+        }
+    }
+
+    #[test]
+    fn test_solve_both() {
+        assert_eq!(solve_both(SAMPLE).unwrap(), (1588, 2188189693529));
+    }
+
+    #[test]
+    fn test_too_many_steps() {
+        // The sample's length after n steps is 3 * 2^n + 1, which fits in a
+        // u128 for up to 126 steps
+        assert!(solve(SAMPLE, 126, Strictness::Strict).is_ok());
+
+        let err = solve(SAMPLE, 130, Strictness::Strict).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "failure at step 127: the polymer would grow too long to count"
+        );
+
+        let (polymer, rules) = parse_input(SAMPLE, Strictness::Strict).unwrap();
+        let mut expander = Expander::new(&rules);
+        assert!(expander.element_counts(&polymer, 126).is_ok());
+        assert!(expander.element_counts(&polymer, 127).is_err());
+
+        // A single element never grows, so it can be stepped forever
+        assert_eq!(
+            solve("N\n\nNN -> N\n", 1000, Strictness::Strict).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(
+            describe(SAMPLE, 3, Strictness::Strict).unwrap(),
+            "most common: B (11)\nleast common: H (4)\nlength after each step: 7, 13, 25"
+        );
+    }
+
+    #[test]
+    fn test_polymer_lengths() {
+        assert_eq!(
+            polymer_lengths(SAMPLE, 5, Strictness::Strict).unwrap(),
+            [7, 13, 25, 49, 97]
+        );
+        assert_eq!(polymer_lengths(SAMPLE, 0, Strictness::Strict).unwrap(), []);
+        assert_eq!(
+            polymer_lengths(SAMPLE, 10, Strictness::Strict)
+                .unwrap()
+                .last()
+                .copied(),
+            Some(
+                element_counts(SAMPLE, 10, Strictness::Strict)
+                    .unwrap()
+                    .total()
+            )
+        );
+    }
+
+    #[test]
+    fn test_many_steps() {
+        // The polymer doubles in length (less one) every step, so after 100
+        // steps it's far too long to count with a u64
+        let steps = 100;
+        let counts = element_counts(SAMPLE, steps, Strictness::Strict).unwrap();
+
+        assert_eq!(counts.total(), 3 * (1 << steps) + 1);
+        assert!(counts.total() > u64::MAX as u128);
+
+        let (polymer, rules) = parse_input(SAMPLE, Strictness::Strict).unwrap();
+        let mut expander = Expander::new(&rules);
+        let expanded = expander.element_counts(&polymer, steps).unwrap();
+
+        assert_eq!(
+            spread(&expanded).unwrap(),
+            solve(SAMPLE, steps, Strictness::Strict).unwrap()
+        );
+    }
+
+    /// The original, sequential version of `apply_rules`
+    fn apply_rules_sequential(polymer: Polymer, rules: &RuleSet) -> anyhow::Result<Polymer> {
+        let pairs = polymer.pairs;
+        let mut counts = polymer.counts;
+
+        pairs
+            .iter_counts()
+            .map(|(&(a, b), count)| rules.insertion(a, b).map(|new| ((a, new, b), count)))
+            .use_oks(move |insertions| {
+                let mut pairs = Counter::new();
+
+                insertions.for_each(|((a, new, b), count)| match new {
+                    Some(new) => {
+                        counts.add(new, count);
+                        pairs.add((a, new), count);
+                        pairs.add((new, b), count);
+                    }
+                    None => pairs.add((a, b), count),
+                });
+
+                Polymer { pairs, counts }
+            })
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        let (polymer, rules) = parse_input(SAMPLE, Strictness::Strict).unwrap();
+
+        let mut parallel = polymer.clone();
+        let mut sequential = polymer;
+
+        for _ in 0..40 {
+            parallel = parallel.apply_rules(&rules).unwrap();
+            sequential = apply_rules_sequential(sequential, &rules).unwrap();
+
+            assert_eq!(parallel.pairs, sequential.pairs);
+            assert_eq!(parallel.counts, sequential.counts);
+        }
+    }
+
+    #[test]
+    fn test_missing_rule_is_deterministic() {
+        // None of the pairs have rules; the error should always name the
+        // first one alphabetically
+        let input = "XaWbVcUdTeSfRgQhPi\n\nAaBb -> Cc\n";
+
+        for _ in 0..20 {
+            let err = part1(input).unwrap_err();
+            assert_eq!(
+                format!("{:#}", err),
+                "failure at step 1: no matching rule QhPi"
+            );
+        }
+    }
+
+    #[test]
+    fn test_lenient() {
+        // There are no rules for NC or CB
+        let input = "NNCB\n\nNN -> C\nCC -> N\n";
+
+        let (polymer, rules) = parse_input(input, Strictness::Strict).unwrap();
+        assert!(polymer.apply_steps(&rules, 1).is_err());
+        assert_eq!(
+            format!("{:#}", solve(input, 1, Strictness::Strict).unwrap_err()),
+            "failure at step 1: no matching rule CB"
+        );
+
+        let (polymer, rules) = parse_input(input, Strictness::Lenient).unwrap();
+
+        // NNCB -> NCNCB -> NCNCB
+        let stepped = polymer.clone().apply_steps(&rules, 2).unwrap();
+        let expected: Counter<&str, u128> = [("N", 2), ("C", 2), ("B", 1)].into_iter().collect();
+
+        let names: Counter<&str, u128> = stepped
+            .counts
+            .iter_counts()
+            .map(|(&chem, count)| (rules.elements.name(chem), count))
+            .collect();
+
+        assert_eq!(names, expected);
+
+        let mut expander = Expander::new(&rules);
+        assert_eq!(
+            expander.element_counts(&polymer, 2).unwrap(),
+            stepped.counts
+        );
+    }
+
+    #[test]
+    fn test_multi_letter_elements() {
+        // The sample, with every element renamed to a two letter name
+        let input = SAMPLE
+            .replace('B', "Be")
+            .replace('C', "Co")
+            .replace('H', "He")
+            .replace('N', "Ni");
+
+        assert_eq!(part1(&input).unwrap(), 1588);
+
+        let counts = element_counts(&input, 10, Strictness::Strict).unwrap();
+        let expected: Counter<&str, u128> = [("Be", 1749), ("Co", 298), ("He", 161), ("Ni", 865)]
+            .into_iter()
+            .collect();
+
+        assert_eq!(counts, expected);
+    }
+
+    #[test]
+    fn test_missing_rule() {
+        let err = part1("FeNiCo\n\nFeNi -> Co\n").unwrap_err();
+
+        assert_eq!(
+            format!("{:#}", err),
+            "failure at step 1: no matching rule NiCo"
+        );
+    }
 }
@@ -1,60 +1,24 @@
-use anyhow::Context;
+use std::collections::HashSet;
+
+use anyhow::{ensure, Context};
 use gridly::prelude::*;
 use gridly_grids::VecGrid;
-use itertools::Itertools;
 use pathfinding::directed::astar::astar;
 
-fn parse_map(input: &str) -> anyhow::Result<VecGrid<isize>> {
-    let cells: Vec<Vec<isize>> = input
-        .lines()
-        .enumerate()
-        .map(|(row, line)| {
-            line.chars()
-                .enumerate()
-                .map(|(column, c)| {
-                    c.to_digit(10)
-                        .map(|d| d as isize)
-                        .with_context(|| format!("failed to parse digit at column {}", column))
-                })
-                .try_collect()
-                .with_context(|| format!("parse error in row {}", row))
-        })
-        .try_collect()
-        .context("error parsing digit in grid")?;
-
-    VecGrid::new_from_rows(cells).context("inconsistent row length")
-}
-
-pub fn part1(input: &str) -> anyhow::Result<isize> {
-    let map = parse_map(input).context("error parsing map")?;
+use crate::{
+    grid::{neighbors, parse_digit_grid, render_grid_with_locations},
+    Part, Solver,
+};
 
-    let start = map.root();
-    let end = map.outer_bound() - (1, 1);
+/// Build the full map by repeating the tile `factor` times in each direction.
+/// Each repetition's risk levels are increased by its manhattan distance (in
+/// tiles) from the original tile, wrapping back around to 1 after 9.
+fn tiled_map(tile: &VecGrid<isize>, factor: isize) -> anyhow::Result<VecGrid<isize>> {
+    ensure!(factor > 0, "tile factor must be positive");
 
-    astar(
-        // Start location
-        &start,
-        // For a given location, an iterator over the possible next steps to
-        // take, along with their costs
-        |&location| {
-            EACH_DIRECTION
-                .iter()
-                .map(move |&direction| location + direction)
-                .filter_map(|dest| map.get(dest).ok().map(|&cost| (dest, cost)))
-        },
-        // The approximate cost to get to the destination
-        |&location| (end - location).manhattan_length(),
-        |&location| location == end,
-    )
-    .context("no solution found")
-    .map(|(_route, cost)| cost)
-}
-
-pub fn part2(input: &str) -> anyhow::Result<isize> {
-    let tile = parse_map(input).context("error parsing map")?;
     let tile_dimensions = tile.dimensions();
 
-    let map = VecGrid::new_with(tile.dimensions() * 5, |location| {
+    VecGrid::new_with(tile_dimensions * factor, |location| {
         let tile_location = Location::new(
             location.row.0 / tile_dimensions.rows.0,
             location.column.0 / tile_dimensions.columns.0,
@@ -71,8 +35,19 @@ pub fn part2(input: &str) -> anyhow::Result<isize> {
 
         ((base_value - 1 + tile_distance) % 9) + 1
     })
-    .context("grid too large")?;
+    .context("grid too large")
+}
+
+/// Parse the input and tile it `factor` times
+fn parse_map(input: &str, factor: isize) -> anyhow::Result<VecGrid<isize>> {
+    let tile: VecGrid<isize> = parse_digit_grid(input).context("error parsing map")?;
+    tiled_map(&tile, factor)
+}
 
+/// Find the lowest-risk path from the top left to the bottom right of the
+/// map, and its total risk. The path includes both ends; the risk doesn't
+/// include the starting location, since it's never entered.
+fn find_path(map: &VecGrid<isize>) -> anyhow::Result<(Vec<Location>, isize)> {
     let start = map.root();
     let end = map.outer_bound() - (1, 1);
 
@@ -81,16 +56,147 @@ pub fn part2(input: &str) -> anyhow::Result<isize> {
         &start,
         // For a given location, an iterator over the possible next steps to
         // take, along with their costs
-        |&location| {
-            EACH_DIRECTION
-                .iter()
-                .map(move |&direction| location + direction)
-                .filter_map(|dest| map.get(dest).ok().map(|&cost| (dest, cost)))
-        },
+        |&location| neighbors(map, location, &EACH_DIRECTION).map(|(dest, &cost)| (dest, cost)),
         // The approximate cost to get to the destination
         |&location| (end - location).manhattan_length(),
         |&location| location == end,
     )
     .context("no solution found")
-    .map(|(_route, cost)| cost)
+}
+
+/// Find the lowest-risk path through the map made by tiling the input
+/// `factor` times, along with its total risk
+pub fn lowest_risk_path(input: &str, factor: isize) -> anyhow::Result<(Vec<Location>, isize)> {
+    find_path(&parse_map(input, factor)?)
+}
+
+/// Find the total risk of the lowest-risk path from the top left to the
+/// bottom right of the map made by tiling the input `factor` times
+pub fn lowest_risk(input: &str, factor: isize) -> anyhow::Result<isize> {
+    lowest_risk_path(input, factor).map(|(_path, risk)| risk)
+}
+
+/// Render the map with only the risk levels along the path shown
+fn render_path(map: &VecGrid<isize>, path: &[Location]) -> String {
+    let path: HashSet<Location> = path.iter().copied().collect();
+
+    render_grid_with_locations(map, |location, &risk| match path.contains(&location) {
+        true => char::from_digit(risk as u32, 10).unwrap_or('#'),
+        false => '.',
+    })
+}
+
+/// The tile factor for each part
+fn tile_factor(part: Part) -> isize {
+    match part {
+        Part::Part1 => 1,
+        Part::Part2 => 5,
+    }
+}
+
+pub fn part1(input: &str) -> anyhow::Result<isize> {
+    lowest_risk(input, tile_factor(Part::Part1))
+}
+
+pub fn part2(input: &str) -> anyhow::Result<isize> {
+    lowest_risk(input, tile_factor(Part::Part2))
+}
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
+
+    fn details(&self, part: Part, input: &str) -> anyhow::Result<Option<String>> {
+        let map = parse_map(input, tile_factor(part))?;
+        let (path, _risk) = find_path(&map)?;
+
+        Ok(Some(render_path(&map, &path)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    const SAMPLE: &str = "\
+1163751742
+1381373672
+2136511328
+3694931569
+7463417111
+1319128137
+1359912421
+3125421639
+1293138521
+2311944581
+";
+
+    #[test]
+    fn test_sample() {
+        assert_eq!(part1(SAMPLE).unwrap(), 40);
+        assert_eq!(part2(SAMPLE).unwrap(), 315);
+    }
+
+    #[test]
+    fn test_tile_factor() {
+        let tile: VecGrid<isize> = parse_digit_grid(SAMPLE).unwrap();
+        let map = tiled_map(&tile, 2).unwrap();
+
+        assert_eq!(map.dimensions(), Rows(20) + Columns(20));
+
+        let first_row: String = map
+            .rows()
+            .iter()
+            .next()
+            .unwrap()
+            .iter()
+            .map(|&risk| char::from_digit(risk as u32, 10).unwrap())
+            .collect();
+        assert_eq!(first_row, "11637517422274862853");
+
+        assert_eq!(lowest_risk(SAMPLE, 2).unwrap(), 101);
+        assert!(lowest_risk(SAMPLE, 0).is_err());
+    }
+
+    #[test]
+    fn test_lowest_risk_path() {
+        let (path, risk) = lowest_risk_path(SAMPLE, 1).unwrap();
+
+        assert_eq!(risk, 40);
+        assert_eq!(path.first(), Some(&Location::zero()));
+        assert_eq!(path.last(), Some(&Location::new(9, 9)));
+
+        // Each step is to an adjacent location, and the total risk is the sum
+        // of the risks of every location entered
+        let map = parse_map(SAMPLE, 1).unwrap();
+
+        assert!(path
+            .iter()
+            .tuple_windows()
+            .all(|(&a, &b)| (b - a).manhattan_length() == 1));
+        assert_eq!(
+            path[1..]
+                .iter()
+                .map(|&location| *map.get(location).unwrap())
+                .sum::<isize>(),
+            risk
+        );
+    }
+
+    #[test]
+    fn test_render_path() {
+        let map = parse_map("123\n456\n789\n", 1).unwrap();
+        let (path, risk) = find_path(&map).unwrap();
+
+        assert_eq!(risk, 2 + 3 + 6 + 9);
+        assert_eq!(render_path(&map, &path), "123\n..6\n..9");
+    }
 }
@@ -20,7 +20,7 @@ use nom_supreme::{
 };
 use thiserror::Error;
 
-use crate::library::IterExt;
+use crate::{library::IterExt, Solver};
 
 #[derive(Debug, Clone)]
 enum PacketData {
@@ -372,3 +372,15 @@ pub fn part2(input: &str) -> anyhow::Result<u64> {
 
     Ok(packet.value())
 }
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
+}
@@ -1,4 +1,4 @@
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use nom::{
     branch::alt,
     character::complete::{char, digit1, multispace0},
@@ -11,6 +11,8 @@ use nom_supreme::{
     ParserExt,
 };
 
+use crate::{library::format_parse_error, Solver};
+
 #[derive(Debug, Clone)]
 enum Element {
     Regular(i64),
@@ -36,7 +38,7 @@ impl Element {
         }
     }
 
-    fn begin_explode(&mut self, left_receiver: Option<&mut i64>, depth: i32) -> ExplodeOutcome {
+    fn begin_explode(&mut self, left_receiver: Option<&mut i64>, depth: i32) -> ExplodeOutcome<'_> {
         // Check if this element can explode
         if depth >= 4 {
             if let Some([left_payload, right_payload]) = self.get_regular_pair() {
@@ -117,7 +119,7 @@ impl Pair {
         paired
     }
 
-    fn begin_explode(&mut self, left_receiver: Option<&mut i64>, depth: i32) -> ExplodeOutcome {
+    fn begin_explode(&mut self, left_receiver: Option<&mut i64>, depth: i32) -> ExplodeOutcome<'_> {
         let [left, right] = &mut self.elements;
 
         match left.begin_explode(left_receiver, depth + 1) {
@@ -223,7 +225,9 @@ fn final_parse_pair_list(input: &str) -> Result<Vec<Pair>, ErrorTree<Location>>
 }
 
 pub fn part1(input: &str) -> anyhow::Result<i64> {
-    let pairs = final_parse_pair_list(input).context("parse error")?;
+    let pairs = final_parse_pair_list(input)
+        .map_err(|err| anyhow!(format_parse_error(input, &err)))
+        .context("parse error")?;
     pairs
         .into_iter()
         .reduce(Pair::add)
@@ -232,7 +236,9 @@ pub fn part1(input: &str) -> anyhow::Result<i64> {
 }
 
 pub fn part2(input: &str) -> anyhow::Result<i64> {
-    let pairs = final_parse_pair_list(input).context("parse error")?;
+    let pairs = final_parse_pair_list(input)
+        .map_err(|err| anyhow!(format_parse_error(input, &err)))
+        .context("parse error")?;
 
     pairs
         .iter()
@@ -249,3 +255,15 @@ pub fn part2(input: &str) -> anyhow::Result<i64> {
         .max()
         .context("no pairs in input")
 }
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
+}
@@ -1,52 +1,117 @@
-use anyhow::Context;
+use std::{fmt, num::ParseIntError, str::FromStr};
+
+use anyhow::{anyhow, Context};
+use itertools::Itertools;
 use nom::{
-    branch::alt,
-    character::complete::{char, digit1, space1},
+    bytes::complete::take_till1,
+    character::complete::{alpha1, char, space1},
     combinator::eof,
     IResult, Parser,
 };
 use nom_supreme::{
     error::ErrorTree,
     final_parser::{final_parser, Location},
-    multi::parse_separated_terminated,
-    tag::complete::tag,
+    multi::collect_separated_terminated,
     ParserExt,
 };
+use thiserror::Error;
+
+use crate::{library::format_parse_error, Part, Solver};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Direction {
+pub enum Direction {
     Forward,
     Down,
     Up,
 }
 
+#[derive(Debug, Error)]
+pub enum CmdError {
+    #[error("unknown direction {0:?}; expected \"forward\", \"down\", or \"up\"")]
+    UnknownDirection(String),
+
+    #[error("missing distance after the direction")]
+    MissingDistance,
+
+    #[error("invalid distance {0:?}; expected a non-negative integer")]
+    InvalidDistance(String),
+
+    #[error("distance out of range: {0}")]
+    DistanceOutOfRange(#[from] ParseIntError),
+}
+
+impl FromStr for Direction {
+    type Err = CmdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "forward" => Ok(Direction::Forward),
+            "down" => Ok(Direction::Down),
+            "up" => Ok(Direction::Up),
+            word => Err(CmdError::UnknownDirection(word.to_owned())),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Cmd {
-    direction: Direction,
-    distance: i32,
+pub struct Cmd {
+    pub direction: Direction,
+    pub distance: i32,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-struct Position {
-    horizontal: i32,
-    depth: i32,
-    aim: i32,
+pub struct Position {
+    pub horizontal: i32,
+    pub depth: i32,
+    pub aim: i32,
+}
+
+impl Position {
+    /// The puzzle answer for a final position
+    pub fn product(&self) -> i32 {
+        self.depth * self.horizontal
+    }
 }
 
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "horizontal: {}, depth: {}, aim: {}",
+            self.horizontal, self.depth, self.aim
+        )
+    }
+}
+
+/// Parse a whole word as a direction, so that an unknown direction is
+/// reported as such rather than as a failure to match any of the tags
 fn parse_direction(input: &str) -> IResult<&str, Direction, ErrorTree<&str>> {
-    alt((
-        tag("forward").value(Direction::Forward),
-        tag("down").value(Direction::Down),
-        tag("up").value(Direction::Up),
-    ))
-    .context("direction")
-    .parse(input)
+    alpha1
+        .map_res(|word: &str| word.parse())
+        .context("direction")
+        .parse(input)
+}
+
+/// Parse the whole word after the direction, so that a present but malformed
+/// distance is reported as invalid rather than missing
+fn parse_distance(input: &str) -> IResult<&str, i32, ErrorTree<&str>> {
+    space1
+        .precedes(take_till1(|c: char| c.is_whitespace()))
+        .opt()
+        .map_res(|word: Option<&str>| match word {
+            None => Err(CmdError::MissingDistance),
+            Some(word) if !word.bytes().all(|b| b.is_ascii_digit()) => {
+                Err(CmdError::InvalidDistance(word.to_owned()))
+            }
+            Some(digits) => digits.parse().map_err(CmdError::DistanceOutOfRange),
+        })
+        .context("distance")
+        .parse(input)
 }
 
 fn parse_cmd(input: &str) -> IResult<&str, Cmd, ErrorTree<&str>> {
     parse_direction
-        .terminated(space1)
-        .and(digit1.parse_from_str().context("distance"))
+        .and(parse_distance)
         .map(|(direction, distance)| Cmd {
             direction,
             distance,
@@ -55,21 +120,21 @@ fn parse_cmd(input: &str) -> IResult<&str, Cmd, ErrorTree<&str>> {
         .parse(input)
 }
 
-fn parse_cmd_list<'a>(
-    func: impl Fn(Position, Cmd) -> Position,
-) -> impl Parser<&'a str, Position, ErrorTree<&'a str>> {
-    parse_separated_terminated(parse_cmd, char('\n'), eof, Position::default, func)
+fn parse_cmd_list(input: &str) -> IResult<&str, Vec<Cmd>, ErrorTree<&str>> {
+    collect_separated_terminated(parse_cmd, char('\n'), eof).parse(input)
 }
 
-fn solve(input: &str, func: impl Fn(Position, Cmd) -> Position) -> anyhow::Result<i32> {
-    let mut parser = final_parser(parse_cmd_list(func));
-    let final_pos: Result<Position, ErrorTree<Location>> = parser(input.trim_end());
-    let final_pos = final_pos.context("parse error")?;
-    Ok(final_pos.depth * final_pos.horizontal)
+fn parse_commands(input: &str) -> anyhow::Result<Vec<Cmd>> {
+    let commands: Result<Vec<Cmd>, ErrorTree<Location>> =
+        final_parser(parse_cmd_list)(input.trim_end());
+
+    commands
+        .map_err(|err| anyhow!(format_parse_error(input, &err)))
+        .context("parse error")
 }
 
-pub fn part1(input: &str) -> anyhow::Result<i32> {
-    solve(input, |pos, cmd| match cmd.direction {
+pub fn basic_rules(pos: Position, cmd: Cmd) -> Position {
+    match cmd.direction {
         Direction::Forward => Position {
             horizontal: pos.horizontal + cmd.distance,
             ..pos
@@ -82,11 +147,11 @@ pub fn part1(input: &str) -> anyhow::Result<i32> {
             depth: pos.depth - cmd.distance,
             ..pos
         },
-    })
+    }
 }
 
-pub fn part2(input: &str) -> anyhow::Result<i32> {
-    solve(input, |pos, cmd| match cmd.direction {
+pub fn aim_rules(pos: Position, cmd: Cmd) -> Position {
+    match cmd.direction {
         Direction::Forward => Position {
             horizontal: pos.horizontal + cmd.distance,
             depth: pos.depth + (pos.aim * cmd.distance),
@@ -100,5 +165,178 @@ pub fn part2(input: &str) -> anyhow::Result<i32> {
             aim: pos.aim - cmd.distance,
             ..pos
         },
-    })
+    }
+}
+
+/// Apply all of the commands in the input, starting from the origin, and
+/// return where the submarine ends up
+pub fn final_position(
+    input: &str,
+    rules: impl Fn(Position, Cmd) -> Position,
+) -> anyhow::Result<Position> {
+    Ok(parse_commands(input)?
+        .into_iter()
+        .fold(Position::default(), rules))
+}
+
+/// Apply all of the commands in the input, returning every position the
+/// submarine passes through, starting with the origin
+pub fn trajectory(
+    input: &str,
+    rules: impl Fn(Position, Cmd) -> Position,
+) -> anyhow::Result<Vec<Position>> {
+    let commands = parse_commands(input)?;
+
+    let mut path = Vec::with_capacity(commands.len() + 1);
+    path.push(Position::default());
+    path.extend(commands.into_iter().scan(Position::default(), |pos, cmd| {
+        *pos = rules(*pos, cmd);
+        Some(*pos)
+    }));
+
+    Ok(path)
+}
+
+/// Solve both parts at once, parsing the commands only once
+fn solve_both(input: &str) -> anyhow::Result<(i32, i32)> {
+    let (basic, aimed) = parse_commands(input)?.into_iter().fold(
+        (Position::default(), Position::default()),
+        |(basic, aimed), cmd| (basic_rules(basic, cmd), aim_rules(aimed, cmd)),
+    );
+
+    Ok((basic.product(), aimed.product()))
+}
+
+pub fn part1(input: &str) -> anyhow::Result<i32> {
+    final_position(input, basic_rules).map(|pos| pos.product())
+}
+
+pub fn part2(input: &str) -> anyhow::Result<i32> {
+    final_position(input, aim_rules).map(|pos| pos.product())
+}
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
+
+    fn both(&self, input: &str) -> anyhow::Result<(String, String)> {
+        let (part1, part2) = solve_both(input)?;
+        Ok((part1.to_string(), part2.to_string()))
+    }
+
+    fn details(&self, part: Part, input: &str) -> anyhow::Result<Option<String>> {
+        let path = match part {
+            Part::Part1 => trajectory(input, basic_rules),
+            Part::Part2 => trajectory(input, aim_rules),
+        }?;
+
+        Ok(Some(path.iter().join("\n")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "forward 5\ndown 5\nforward 8\nup 3\ndown 8\nforward 2\n";
+
+    #[test]
+    fn test_solve_both() {
+        assert_eq!(part1(SAMPLE).unwrap(), 150);
+        assert_eq!(part2(SAMPLE).unwrap(), 900);
+        assert_eq!(
+            solve_both(SAMPLE).unwrap(),
+            (part1(SAMPLE).unwrap(), part2(SAMPLE).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_final_position() {
+        let pos = final_position(SAMPLE, aim_rules).unwrap();
+
+        assert_eq!(pos.horizontal, 15);
+        assert_eq!(pos.depth, 60);
+    }
+
+    #[test]
+    fn test_trajectory() {
+        let path = trajectory(SAMPLE, aim_rules).unwrap();
+
+        assert_eq!(path.len(), SAMPLE.lines().count() + 1);
+        assert_eq!(path[0], Position::default());
+        assert_eq!(
+            path.last(),
+            Some(&final_position(SAMPLE, aim_rules).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_parse_error_caret() {
+        let err = part1("forward 5\nbackward 3\ndown 2").unwrap_err();
+        let message = format!("{:#}", err);
+
+        assert!(
+            message.contains("at line 2, column 1\n    backward 3\n    ^"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_unknown_direction() {
+        let err = part1("forward 5\nbackward 3\ndown 2").unwrap_err();
+        let message = format!("{:#}", err);
+
+        assert!(
+            message.contains(
+                "unknown direction \"backward\"; expected \"forward\", \"down\", or \"up\" \
+                at line 2, column 1"
+            ),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_missing_distance() {
+        let err = part1("forward 5\nforward\ndown 2").unwrap_err();
+        let message = format!("{:#}", err);
+
+        assert!(
+            message.contains("missing distance after the direction at line 2, column 8"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_invalid_distance() {
+        for (input, word) in [
+            ("forward 5\nforward x\ndown 2", "x"),
+            ("forward 5\nforward -5\ndown 2", "-5"),
+        ] {
+            let err = part1(input).unwrap_err();
+            let message = format!("{:#}", err);
+
+            assert!(
+                message.contains(&format!(
+                    "invalid distance {:?}; expected a non-negative integer at line 2, column 8",
+                    word
+                )),
+                "{}",
+                message
+            );
+        }
+
+        let err = part1("forward 99999999999").unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(message.contains("distance out of range"), "{}", message);
+    }
 }
@@ -12,9 +12,11 @@ use nom_supreme::{
     tag::complete::tag,
     ParserExt,
 };
-use rayon::prelude::*;
 
-use crate::library::{Counter, IterExt};
+use crate::{
+    library::{Counter, IterExt},
+    Solver,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Enum, Hash)]
 enum Player {
@@ -65,7 +67,7 @@ impl State {
         self.position.add(amount);
         self.score += self.position.value();
 
-        (self.score >= winning_score).then(|| Win)
+        (self.score >= winning_score).then_some(Win)
     }
 }
 
@@ -132,9 +134,8 @@ impl<I: Iterator> Iterator for IterCounter<I> {
     type Item = I::Item;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.iter.next().map(|item| {
+        self.iter.next().inspect(|_| {
             self.count += 1;
-            item
         })
     }
 
@@ -271,3 +272,15 @@ pub fn part2(input: &str) -> anyhow::Result<usize> {
         .max()
         .unwrap())
 }
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
+}
@@ -1,6 +1,6 @@
 use std::cmp;
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use enum_map::{enum_map, Enum, EnumMap};
 use nom::{
     branch::alt,
@@ -16,6 +16,8 @@ use nom_supreme::{
 };
 use rayon::prelude::*;
 
+use crate::Solver;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Enum)]
 enum Axis {
     X,
@@ -94,18 +96,13 @@ fn parse_cube(input: &str) -> IResult<&str, Cube, ErrorTree<&str>> {
         .parse(input)
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 enum State {
+    #[default]
     Off,
     On,
 }
 
-impl Default for State {
-    fn default() -> Self {
-        State::Off
-    }
-}
-
 fn parse_state(input: &str) -> IResult<&str, State, ErrorTree<&str>> {
     alt((tag("on").value(State::On), tag("off").value(State::Off))).parse(input)
 }
@@ -169,6 +166,18 @@ pub fn part1(input: &str) -> anyhow::Result<usize> {
     Ok(count)
 }
 
-pub fn part2(input: &str) -> anyhow::Result<usize> {
-    todo!()
+pub fn part2(_input: &str) -> anyhow::Result<usize> {
+    bail!("part 2 isn't solved yet")
+}
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
 }
@@ -1,59 +1,102 @@
-use anyhow::Context;
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use anyhow::{bail, ensure, Context};
 use itertools::{self, Itertools};
+use thiserror::Error;
 
-use crate::library::{IterExt, StrExt};
+use crate::{library::StrExt, Part, Solver};
 
-#[derive(Default)]
-struct Counts {
-    ones: u32,
-}
+/// Split the input into signals, skipping blank lines and checking that every
+/// signal contains only binary digits and is the same width as the first one.
+fn parse_signals(input: &str) -> anyhow::Result<Vec<&str>> {
+    let mut signals = Vec::new();
+
+    // The line number and width of the first signal
+    let mut first: Option<(usize, usize)> = None;
+
+    let lines = input
+        .lines()
+        .enumerate()
+        .map(|(index, signal)| (index + 1, signal))
+        .filter(|(_, signal)| !signal.is_empty());
+
+    for (line, signal) in lines {
+        if let Some((column, c)) = signal.char_indices().find(|&(_, c)| c != '0' && c != '1') {
+            bail!(
+                "invalid bit {:?} at line {}, column {}",
+                c,
+                line,
+                column + 1
+            );
+        }
 
-struct IterCounter<'a, I> {
-    iter: I,
-    count: &'a mut u32,
+        let (first_line, width) = *first.get_or_insert((line, signal.len()));
+
+        ensure!(
+            signal.len() == width,
+            "line {} has width {}, but line {} has width {}",
+            line,
+            signal.len(),
+            first_line,
+            width
+        );
+
+        signals.push(signal);
+    }
+
+    ensure!(!signals.is_empty(), "no signals in input");
+
+    Ok(signals)
 }
 
-impl<'a, I: Iterator> Iterator for IterCounter<'a, I> {
-    type Item = I::Item;
+/// The rates and ratings read out of the diagnostic report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Diagnostics {
+    pub gamma: u32,
+    pub epsilon: u32,
+    pub o2: u32,
+    pub co2: u32,
+}
 
-    fn next(&mut self) -> Option<I::Item> {
-        self.iter.next().map(|item| {
-            *self.count += 1;
-            item
-        })
+impl Diagnostics {
+    pub fn life_support_rating(&self) -> u32 {
+        self.o2 * self.co2
     }
+}
 
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        self.iter.size_hint()
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "gamma: {}, epsilon: {}, o2: {}, co2: {}",
+            self.gamma, self.epsilon, self.o2, self.co2
+        )
     }
 }
 
-pub fn part1(input: &str) -> anyhow::Result<u32> {
-    let mut signal_count = 0;
-    let signals = IterCounter {
-        iter: input.lines(),
-        count: &mut signal_count,
-    };
-
-    let signals = signals.fold(Vec::new(), |mut counts, signal| {
-        if counts.is_empty() {
-            counts.resize_with(signal.len(), Counts::default);
-        }
+/// Compute the gamma and epsilon rates from the most and least common bit in
+/// each column
+fn power_rates(signals: &[&str]) -> anyhow::Result<(u32, u32)> {
+    let signal_count = signals.len() as u32;
+
+    // parse_signals guarantees at least one signal, and that they're all the
+    // same nonzero width
+    let width = signals[0].len();
+    let mut ones_counts = vec![0u32; width];
 
-        counts
+    for signal in signals {
+        ones_counts
             .iter_mut()
             .zip(signal.bytes())
             .filter(|&(_, b)| b == b'1')
-            .for_each(|(slot, _)| slot.ones += 1);
-
-        counts
-    });
+            .for_each(|(count, _)| *count += 1);
+    }
 
-    let (gamma_rate, epsilon_rate): (String, String) = signals
+    let (gamma_rate, epsilon_rate): (String, String) = ones_counts
         .iter()
-        .map(|column| {
-            let zero_count = signal_count - column.ones;
-            column.ones > zero_count
+        .map(|&ones_count| {
+            let zero_count = signal_count - ones_count;
+            ones_count > zero_count
         })
         .map(|b| if b { ('1', '0') } else { ('0', '1') })
         .unzip();
@@ -66,12 +109,57 @@ pub fn part1(input: &str) -> anyhow::Result<u32> {
         .parse_radix(2)
         .context("failed to parse binary number")?;
 
-    Ok(gamma_rate * epsilon_rate)
+    Ok((gamma_rate, epsilon_rate))
+}
+
+/// Which bit is considered the most common in a column that has exactly as
+/// many ones as zeroes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TieBreak {
+    OnesWin,
+    ZerosWin,
+}
+
+#[derive(Debug, Clone, Error)]
+#[error("{0:?} is not a tie break; must be \"ones\" or \"zeros\"")]
+pub struct TieBreakError(String);
+
+impl FromStr for TieBreak {
+    type Err = TieBreakError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ones" => Ok(TieBreak::OnesWin),
+            "zeros" => Ok(TieBreak::ZerosWin),
+            _ => Err(TieBreakError(s.to_owned())),
+        }
+    }
+}
+
+impl TieBreak {
+    /// Get the most common bit in a column with these counts
+    fn most_common(self, ones_count: usize, zeroes_count: usize) -> bool {
+        match ones_count.cmp(&zeroes_count) {
+            Ordering::Greater => true,
+            Ordering::Less => false,
+            Ordering::Equal => self == TieBreak::OnesWin,
+        }
+    }
 }
 
-/// bit_criteria is a function taking (column_bit, bit)
+/// bit_criteria is a function taking (column_bit, bit), where column_bit is
+/// the most common bit in the column, as decided by `tie_break` if the column
+/// is tied. A criteria keeping signals where `column_bit == bit` keeps the
+/// tie-break winner on a tie, while one keeping `column_bit != bit` keeps the
+/// loser; this is how the o2 rating ends up keeping ones and the co2 rating
+/// zeroes, with the same `TieBreak::OnesWin`.
+///
+/// Each bit takes a single pass over the surviving signals, partitioning them
+/// by that bit and keeping the chosen partition, so the whole search is
+/// O(n·w) rather than rescanning and retaining separately at every bit.
 fn identify_diagnostic_code(
     mut signals: Vec<&str>,
+    tie_break: TieBreak,
     bit_criteria: impl Fn(bool, bool) -> bool,
 ) -> Option<&str> {
     for i in 0.. {
@@ -79,20 +167,31 @@ fn identify_diagnostic_code(
             return signal.copied();
         }
 
-        // Count the true bits in column `i`, but also return `None` if `i`
-        // is out of bounds for the column
-        let ones_count = signals
-            .iter()
-            .map(|signal| signal.as_bytes().get(i))
-            .map(|bit| bit.ok_or(()))
-            .use_oks(|column_bits| column_bits.filter(|&&b| b == b'1').count())
-            .ok()?;
+        let mut ones = Vec::with_capacity(signals.len());
+        let mut zeroes = Vec::with_capacity(signals.len());
+
+        // Return `None` if `i` is out of bounds for the column
+        for signal in signals {
+            match *signal.as_bytes().get(i)? {
+                b'1' => ones.push(signal),
+                _ => zeroes.push(signal),
+            }
+        }
 
-        let zeroes_count = signals.len() - ones_count;
+        let column_bit = tie_break.most_common(ones.len(), zeroes.len());
 
-        signals.retain(|signal| {
-            bit_criteria(ones_count >= zeroes_count, signal.as_bytes()[i] == b'1')
-        });
+        signals = match (
+            bit_criteria(column_bit, true),
+            bit_criteria(column_bit, false),
+        ) {
+            (true, true) => {
+                ones.extend(zeroes);
+                ones
+            }
+            (true, false) => ones,
+            (false, true) => zeroes,
+            (false, false) => Vec::new(),
+        };
     }
 
     None
@@ -100,27 +199,251 @@ fn identify_diagnostic_code(
 
 fn parse_diagnostic_code(
     signals: Vec<&str>,
+    tie_break: TieBreak,
     bit_criteria: impl Fn(bool, bool) -> bool,
 ) -> anyhow::Result<u32> {
-    identify_diagnostic_code(signals, bit_criteria)
+    identify_diagnostic_code(signals, tie_break, bit_criteria)
         .context("no rating found")?
         .parse_radix(2)
         .context("failed to parse rating")
 }
 
-pub fn part2(input: &str) -> anyhow::Result<u32> {
-    let input = input.lines().collect_vec();
+/// Compute the oxygen generator and CO2 scrubber ratings by filtering the
+/// signals on their bit criteria
+fn life_support_ratings(signals: &[&str], tie_break: TieBreak) -> anyhow::Result<(u32, u32)> {
+    let o2_rating: u32 =
+        parse_diagnostic_code(signals.to_vec(), tie_break, |column_bit, signal_bit| {
+            column_bit == signal_bit
+        })
+        .context("error getting o2 rating")?;
+
+    let co2_rating: u32 =
+        parse_diagnostic_code(signals.to_vec(), tie_break, |column_bit, signal_bit| {
+            column_bit != signal_bit
+        })
+        .context("error getting co2 rating")?;
+
+    Ok((o2_rating, co2_rating))
+}
+
+/// Read the diagnostics, using `tie_break` to decide the most common bit in
+/// tied columns while finding the life support ratings
+pub fn diagnostics_with(input: &str, tie_break: TieBreak) -> anyhow::Result<Diagnostics> {
+    let signals = parse_signals(input)?;
+    let (gamma, epsilon) = power_rates(&signals)?;
+    let (o2, co2) = life_support_ratings(&signals, tie_break)?;
 
-    let o2_rating: u32 = parse_diagnostic_code(input.clone(), |column_bit, signal_bit| {
-        column_bit == signal_bit
+    Ok(Diagnostics {
+        gamma,
+        epsilon,
+        o2,
+        co2,
     })
-    .context("error getting o2 rating")?;
+}
 
-    let co2_rating: u32 =
-        parse_diagnostic_code(input, |column_bit, signal_bit| column_bit != signal_bit)
-            .context("error getting co2 rating")?;
+/// Read the diagnostics the way the puzzle does, where ones win ties
+pub fn diagnostics(input: &str) -> anyhow::Result<Diagnostics> {
+    diagnostics_with(input, TieBreak::OnesWin)
+}
+
+/// Part 1 only needs the power rates, so it doesn't fail if the life support
+/// ratings can't be found
+pub fn part1(input: &str) -> anyhow::Result<u32> {
+    let signals = parse_signals(input)?;
+    let (gamma, epsilon) = power_rates(&signals)?;
 
-    eprintln!("o2: {}, co2: {}", o2_rating, co2_rating);
+    Ok(gamma * epsilon)
+}
 
-    Ok(o2_rating * co2_rating)
+pub fn part2(input: &str) -> anyhow::Result<u32> {
+    diagnostics(input).map(|diagnostics| diagnostics.life_support_rating())
+}
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
+
+    fn details(&self, _part: Part, input: &str) -> anyhow::Result<Option<String>> {
+        diagnostics(input).map(|diagnostics| Some(diagnostics.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    use crate::library::IterExt;
+
+    /// The original implementation of `identify_diagnostic_code`, which
+    /// counts and then retains at each bit
+    fn identify_diagnostic_code_by_retain(
+        mut signals: Vec<&str>,
+        tie_break: TieBreak,
+        bit_criteria: impl Fn(bool, bool) -> bool,
+    ) -> Option<&str> {
+        for i in 0.. {
+            if let Ok(signal) = signals.iter().at_most_one() {
+                return signal.copied();
+            }
+
+            let ones_count = signals
+                .iter()
+                .map(|signal| signal.as_bytes().get(i))
+                .map(|bit| bit.ok_or(()))
+                .use_oks(|column_bits| column_bits.filter(|&&b| b == b'1').count())
+                .ok()?;
+
+            let zeroes_count = signals.len() - ones_count;
+            let column_bit = tie_break.most_common(ones_count, zeroes_count);
+
+            signals.retain(|signal| bit_criteria(column_bit, signal.as_bytes()[i] == b'1'));
+        }
+
+        None
+    }
+
+    const SAMPLE: &str = "\
+        00100\n11110\n10110\n10111\n10101\n01111\n\
+        00111\n11100\n10000\n11001\n00010\n01010\n";
+
+    #[test]
+    fn test_diagnostics() {
+        assert_eq!(
+            diagnostics(SAMPLE).unwrap(),
+            Diagnostics {
+                gamma: 22,
+                epsilon: 9,
+                o2: 23,
+                co2: 10,
+            }
+        );
+    }
+
+    #[test]
+    fn test_tie_break() {
+        let tied = || vec!["01", "10"];
+        let keep_equal = |column_bit, signal_bit| column_bit == signal_bit;
+        let keep_different = |column_bit, signal_bit| column_bit != signal_bit;
+
+        assert_eq!(
+            identify_diagnostic_code(tied(), TieBreak::OnesWin, keep_equal),
+            Some("10")
+        );
+        assert_eq!(
+            identify_diagnostic_code(tied(), TieBreak::ZerosWin, keep_equal),
+            Some("01")
+        );
+        assert_eq!(
+            identify_diagnostic_code(tied(), TieBreak::OnesWin, keep_different),
+            Some("01")
+        );
+        assert_eq!(
+            identify_diagnostic_code(tied(), TieBreak::ZerosWin, keep_different),
+            Some("10")
+        );
+    }
+
+    #[test]
+    fn test_zeros_win_diagnostics() {
+        let tie_break: TieBreak = "zeros".parse().unwrap();
+
+        assert_eq!(
+            diagnostics_with(SAMPLE, tie_break).unwrap(),
+            Diagnostics {
+                gamma: 22,
+                epsilon: 9,
+                o2: 22,
+                co2: 15,
+            }
+        );
+        assert_eq!(
+            "neither".parse::<TieBreak>().unwrap_err().to_string(),
+            "\"neither\" is not a tie break; must be \"ones\" or \"zeros\""
+        );
+    }
+
+    #[test]
+    fn test_single_bit_signals() {
+        assert_eq!(
+            diagnostics("1\n0\n").unwrap(),
+            Diagnostics {
+                gamma: 0,
+                epsilon: 1,
+                o2: 1,
+                co2: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_empty_input() {
+        let err = part1("").unwrap_err();
+        assert_eq!(err.to_string(), "no signals in input");
+
+        let err = part2("\n\n").unwrap_err();
+        assert_eq!(err.to_string(), "no signals in input");
+    }
+
+    #[test]
+    fn test_blank_lines_skipped() {
+        let input = format!("\n{}\n\n", SAMPLE.replace("\n10110\n", "\n\n10110\n"));
+        assert_eq!(diagnostics(&input).unwrap(), diagnostics(SAMPLE).unwrap());
+    }
+
+    #[test]
+    fn test_part1_without_ratings() {
+        // The signals are identical, so there's no way to narrow them down to
+        // a single rating, but part 1 doesn't need one
+        assert_eq!(part1("101\n101").unwrap(), 10);
+        assert!(part2("101\n101").is_err());
+    }
+
+    #[test]
+    fn test_short_line() {
+        let err = part1("00100\n1111\n10110").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2 has width 4, but line 1 has width 5"
+        );
+        assert!(part2("00100\n1111\n10110").is_err());
+    }
+
+    #[test]
+    fn test_non_binary_digit() {
+        let err = part2("00100\n11110\n10210").unwrap_err();
+        assert_eq!(err.to_string(), "invalid bit '2' at line 3, column 3");
+        assert!(part1("00100\n11110\n10210").is_err());
+    }
+
+    fn tie_break() -> impl Strategy<Value = TieBreak> {
+        prop_oneof![Just(TieBreak::OnesWin), Just(TieBreak::ZerosWin)]
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn test_partition_matches_retain(
+            values in prop::collection::vec(0u16..4096, 1..2000),
+            tie_break in tie_break(),
+            keep_equal in any::<bool>(),
+        ) {
+            let signals = values.iter().map(|value| format!("{:012b}", value)).collect_vec();
+            let signals = signals.iter().map(String::as_str).collect_vec();
+            let criteria = |column_bit, signal_bit| (column_bit == signal_bit) == keep_equal;
+
+            prop_assert_eq!(
+                identify_diagnostic_code(signals.clone(), tie_break, criteria),
+                identify_diagnostic_code_by_retain(signals, tie_break, criteria)
+            );
+        }
+    }
 }
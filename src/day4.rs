@@ -1,9 +1,13 @@
-use anyhow::Context;
+use std::fmt;
+
+use anyhow::{anyhow, bail, Context};
 use gridly::prelude::*;
 use gridly_grids::ArrayGrid;
+use itertools::Itertools;
 
 use nom::{
     character::complete::{char, digit1, line_ending, multispace1, space0},
+    multi::many1,
     IResult, Parser,
 };
 use nom_supreme::{
@@ -12,6 +16,10 @@ use nom_supreme::{
     multi::collect_separated_terminated,
     ParserExt,
 };
+use rayon::prelude::*;
+use thiserror::Error;
+
+use crate::{grid::render_grid_cells, library::format_parse_error, Part, Solver};
 
 #[derive(Debug, Copy, Clone)]
 struct Cell {
@@ -19,50 +27,74 @@ struct Cell {
     mark: bool,
 }
 
+/// An R×C bingo board
 #[derive(Copy, Clone)]
-struct Board {
-    grid: ArrayGrid<Cell, 5, 5>,
+struct Board<const R: usize, const C: usize> {
+    grid: ArrayGrid<Cell, R, C>,
     win: bool,
 }
 
+/// The line that was completed to win a board
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum WinKind {
+    Row(usize),
+    Column(usize),
+}
+
+impl fmt::Display for WinKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            WinKind::Row(index) => write!(f, "row {}", index),
+            WinKind::Column(index) => write!(f, "column {}", index),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 struct Win {
     score: i32,
+    kind: WinKind,
+
+    /// The number that completed the line
+    number: i32,
 }
 
-impl Board {
+impl<const R: usize, const C: usize> Board<R, C> {
     pub fn mark_number(&mut self, num: i32) -> Option<Win> {
-        // let (cell, location) = Row(0)
-        //     .span(Rows(5))
-        //     .flat_map(|r| Column(0).span(Columns(5)).map(move |c| r + c))
-        //     .find_map(move |loc| self.grid.get_mut(loc).ok().map(|cell| (cell, loc)))?;
-
-        for row in Row(0).span(Rows(5)) {
-            for column in Column(0).span(Columns(5)) {
+        for row in Row(0).span(Rows(R as isize)) {
+            for column in Column(0).span(Columns(C as isize)) {
                 let cell = self.grid.get_mut(row + column).unwrap();
                 if cell.value == num {
                     cell.mark = true;
 
-                    return (self.grid.row(row).unwrap().iter().all(|cell| cell.mark)
-                        || self
+                    let kind = if self.grid.row(row).unwrap().iter().all(|cell| cell.mark) {
+                        WinKind::Row(row.0 as usize)
+                    } else if self
+                        .grid
+                        .column(column)
+                        .unwrap()
+                        .iter()
+                        .all(|cell| cell.mark)
+                    {
+                        WinKind::Column(column.0 as usize)
+                    } else {
+                        return None;
+                    };
+
+                    self.win = true;
+
+                    return Some(Win {
+                        score: self
                             .grid
-                            .column(column)
-                            .unwrap()
+                            .rows()
                             .iter()
-                            .all(|cell| cell.mark))
-                    .then(|| {
-                        self.win = true;
-                        Win {
-                            score: self
-                                .grid
-                                .rows()
-                                .iter()
-                                .flat_map(|row| row.iter())
-                                .filter(|cell| !cell.mark)
-                                .map(|cell| cell.value)
-                                .sum::<i32>()
-                                * num,
-                        }
+                            .flat_map(|row| row.iter())
+                            .filter(|cell| !cell.mark)
+                            .map(|cell| cell.value)
+                            .sum::<i32>()
+                            * num,
+                        kind,
+                        number: num,
                     });
                 }
             }
@@ -70,30 +102,85 @@ impl Board {
 
         None
     }
+
+    /// Find a number that appears on this board more than once. Marking only
+    /// ever marks the first matching cell, so such boards can't be played.
+    fn find_duplicate(&self) -> Option<i32> {
+        self.grid
+            .rows()
+            .iter()
+            .flat_map(|row| row.iter())
+            .map(|cell| cell.value)
+            .duplicates()
+            .next()
+    }
+}
+
+/// Renders each cell's value, with marked cells in brackets
+impl<const R: usize, const C: usize> fmt::Display for Board<R, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .grid
+            .rows()
+            .iter()
+            .flat_map(|row| row.iter())
+            .map(|cell| cell.value.to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        let rendered = render_grid_cells(&self.grid, "", |cell| match cell.mark {
+            true => format!("[{:>width$}]", cell.value, width = width),
+            false => format!(" {:>width$} ", cell.value, width = width),
+        });
+
+        f.write_str(&rendered)
+    }
 }
 
 #[derive(Clone)]
-struct Game {
-    boards: Vec<Board>,
+struct Game<const R: usize, const C: usize> {
+    boards: Vec<Board<R, C>>,
     balls: Vec<i32>,
 }
 
-fn parse_board(input: &str) -> IResult<&str, Board, ErrorTree<&str>> {
-    digit1
-        .preceded_by(space0)
-        .parse_from_str()
-        .map(|value| Cell { value, mark: false })
-        .context("cell")
-        .array()
-        .map(|row: [Cell; 5]| row)
-        .context("row")
+#[derive(Debug, Error)]
+#[error("row has {width} cells, but boards are {expected} cells wide")]
+struct RowWidthError {
+    width: usize,
+    expected: usize,
+}
+
+fn parse_row<const C: usize>(input: &str) -> IResult<&str, [Cell; C], ErrorTree<&str>> {
+    many1(
+        digit1
+            .preceded_by(space0)
+            .parse_from_str()
+            .map(|value| Cell { value, mark: false })
+            .context("cell"),
+    )
+    .map_res(|cells: Vec<Cell>| {
+        <[Cell; C]>::try_from(cells).map_err(|cells| RowWidthError {
+            width: cells.len(),
+            expected: C,
+        })
+    })
+    .context("row")
+    .parse(input)
+}
+
+fn parse_board<const R: usize, const C: usize>(
+    input: &str,
+) -> IResult<&str, Board<R, C>, ErrorTree<&str>> {
+    parse_row
         .separated_array(line_ending)
         .map(ArrayGrid::from_rows)
         .map(|grid| Board { grid, win: false })
         .parse(input)
 }
 
-fn parse_input(input: &str) -> IResult<&str, Game, ErrorTree<&str>> {
+fn parse_input<const R: usize, const C: usize>(
+    input: &str,
+) -> IResult<&str, Game<R, C>, ErrorTree<&str>> {
     collect_separated_terminated(
         digit1.parse_from_str::<i32>().context("ball"),
         char(','),
@@ -113,33 +200,297 @@ fn parse_input(input: &str) -> IResult<&str, Game, ErrorTree<&str>> {
     .parse(input)
 }
 
-pub fn part1(input: &str) -> anyhow::Result<i32> {
-    let game: Result<Game, ErrorTree<Location>> = final_parser(parse_input)(input);
-    let mut game = game.context("error parsing input into game")?;
-
-    game.balls
-        .iter()
-        .find_map(|&ball| {
-            game.boards
-                .iter_mut()
-                .find_map(|board| board.mark_number(ball).map(|win| win.score))
-        })
-        .context("no winning board")
+fn parse_game<const R: usize, const C: usize>(input: &str) -> anyhow::Result<Game<R, C>> {
+    let game: Result<Game<R, C>, ErrorTree<Location>> = final_parser(parse_input)(input);
+
+    let game = game
+        .map_err(|err| anyhow!(format_parse_error(input, &err)))
+        .context("error parsing input into game")?;
+
+    for (index, board) in game.boards.iter().enumerate() {
+        if let Some(value) = board.find_duplicate() {
+            bail!(
+                "board {} contains the number {} more than once",
+                index,
+                value
+            );
+        }
+    }
+
+    Ok(game)
 }
 
-pub fn part2(input: &str) -> anyhow::Result<i32> {
-    let game: Result<Game, ErrorTree<Location>> = final_parser(parse_input)(input);
-    let Game { mut boards, balls } = game.context("error parsing input into game")?;
+/// The index of each board that won, along with its win
+type Wins = Vec<(usize, Win)>;
+
+/// Mark a ball on every board that hasn't won yet, in parallel, returning
+/// the boards that win on this ball. The wins are in order of board index, so
+/// ties between boards winning on the same ball are broken deterministically.
+fn mark_all<const R: usize, const C: usize>(boards: &mut [Board<R, C>], ball: i32) -> Wins {
+    boards
+        .par_iter_mut()
+        .enumerate()
+        .filter(|(_, board)| !board.win)
+        .filter_map(|(index, board)| board.mark_number(ball).map(|win| (index, win)))
+        .collect()
+}
+
+/// Play every ball, returning the index of each board that wins along with its
+/// win, in the order that they win, and the final state of every board.
+/// Boards that never win are omitted from the wins. Boards aren't marked after
+/// they win, so their final state is the state they won in.
+fn play<const R: usize, const C: usize>(input: &str) -> anyhow::Result<(Wins, Vec<Board<R, C>>)> {
+    let Game { mut boards, balls } = parse_game::<R, C>(input)?;
+    let mut wins = Vec::new();
+
+    for ball in balls {
+        wins.extend(mark_all(&mut boards, ball));
+
+        if wins.len() == boards.len() {
+            break;
+        }
+    }
+
+    Ok((wins, boards))
+}
+
+/// Play every ball, returning the index of each board that wins along with its
+/// win, in the order that they win. Boards that never win are omitted.
+fn winning_order<const R: usize, const C: usize>(input: &str) -> anyhow::Result<Wins> {
+    play::<R, C>(input).map(|(wins, _)| wins)
+}
+
+fn first_win_score<const R: usize, const C: usize>(input: &str) -> anyhow::Result<i32> {
+    let Game { mut boards, balls } = parse_game::<R, C>(input)?;
 
     balls
-        .iter()
-        .filter_map(|&ball| {
-            boards
-                .iter_mut()
-                .filter(|board| !board.win)
-                .filter_map(|board| board.mark_number(ball).map(|win| win.score))
-                .last()
-        })
+        .into_iter()
+        .find_map(|ball| mark_all(&mut boards, ball).first().copied())
+        .map(|(_, win)| win.score)
+        .context("no winning board")
+}
+
+fn last_win_score<const R: usize, const C: usize>(input: &str) -> anyhow::Result<i32> {
+    winning_order::<R, C>(input)?
         .last()
+        .map(|(_, win)| win.score)
         .context("no winning board")
 }
+
+pub fn part1(input: &str) -> anyhow::Result<i32> {
+    first_win_score::<5, 5>(input)
+}
+
+pub fn part2(input: &str) -> anyhow::Result<i32> {
+    last_win_score::<5, 5>(input)
+}
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
+
+    fn details(&self, part: Part, input: &str) -> anyhow::Result<Option<String>> {
+        let (wins, boards) = play::<5, 5>(input)?;
+
+        let mut details = wins
+            .iter()
+            .map(|(index, win)| {
+                format!(
+                    "board {} won on {} with {}, scoring {}",
+                    index, win.number, win.kind, win.score
+                )
+            })
+            .join("\n");
+
+        let winner = match part {
+            Part::Part1 => wins.first(),
+            Part::Part2 => wins.last(),
+        };
+
+        if let Some(&(index, _)) = winner {
+            details.push_str(&format!("\n\nboard {}:\n{}", index, boards[index]));
+        }
+
+        Ok(Some(details))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+7,4,9,5,11,17,23,2,0,14,21,24,10,16,13,6,15,25,12,22,18,20,8,19,3,26,1
+
+22 13 17 11  0
+ 8  2 23  4 24
+21  9 14 16  7
+ 6 10  3 18  5
+ 1 12 20 15 19
+
+ 3 15  0  2 22
+ 9 18 13 17  5
+19  8  7 25 23
+20 11 10 24  4
+14 21 16 12  6
+
+14 21 17 24  4
+10 16 15  9 19
+18  8 23 26 20
+22 11 13  6  5
+ 2  0 12  3  7
+";
+
+    const SMALL_SAMPLE: &str = "\
+5,1,9,2,8,13,14
+
+1 2 3
+4 5 6
+7 8 9
+
+10 11 12
+13  5 14
+ 1 15 16
+";
+
+    const RECTANGULAR_SAMPLE: &str = "\
+5,7,2,10
+
+1 2 3
+4 5 6
+
+ 7  8  9
+10 11 12
+";
+
+    #[test]
+    fn test_sample() {
+        assert_eq!(part1(SAMPLE).unwrap(), 4512);
+        assert_eq!(part2(SAMPLE).unwrap(), 1924);
+    }
+
+    #[test]
+    fn test_winning_order() {
+        let wins = winning_order::<5, 5>(SAMPLE).unwrap();
+
+        assert_eq!(wins.len(), 3);
+        assert_eq!(
+            wins.first(),
+            Some(&(
+                2,
+                Win {
+                    score: 4512,
+                    kind: WinKind::Row(0),
+                    number: 24
+                }
+            ))
+        );
+        assert_eq!(
+            wins.last(),
+            Some(&(
+                1,
+                Win {
+                    score: 1924,
+                    kind: WinKind::Column(2),
+                    number: 13
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        let Game { mut boards, balls } = parse_game::<5, 5>(SAMPLE).unwrap();
+        let mut sequential_wins = Vec::new();
+
+        for ball in balls {
+            for (index, board) in boards.iter_mut().enumerate() {
+                if !board.win {
+                    sequential_wins.extend(board.mark_number(ball).map(|win| (index, win)));
+                }
+            }
+        }
+
+        assert_eq!(winning_order::<5, 5>(SAMPLE).unwrap(), sequential_wins);
+        assert_eq!(part1(SAMPLE).unwrap(), sequential_wins[0].1.score);
+        assert_eq!(
+            part2(SAMPLE).unwrap(),
+            sequential_wins.last().unwrap().1.score
+        );
+    }
+
+    #[test]
+    fn test_3x3_boards() {
+        assert_eq!(first_win_score::<3, 3>(SMALL_SAMPLE).unwrap(), 160);
+        assert_eq!(last_win_score::<3, 3>(SMALL_SAMPLE).unwrap(), 896);
+    }
+
+    #[test]
+    fn test_2x3_boards() {
+        assert_eq!(first_win_score::<2, 3>(RECTANGULAR_SAMPLE).unwrap(), 28);
+        assert_eq!(last_win_score::<2, 3>(RECTANGULAR_SAMPLE).unwrap(), 400);
+    }
+
+    #[test]
+    fn test_column_win() {
+        let wins = winning_order::<2, 3>(RECTANGULAR_SAMPLE).unwrap();
+
+        assert_eq!(
+            wins,
+            [
+                (
+                    0,
+                    Win {
+                        score: 28,
+                        kind: WinKind::Column(1),
+                        number: 2
+                    }
+                ),
+                (
+                    1,
+                    Win {
+                        score: 400,
+                        kind: WinKind::Column(0),
+                        number: 10
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_number() {
+        let err = first_win_score::<2, 3>("1,2\n\n1 2 3\n4 5 6\n\n7 8 9\n10 8 11\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "board 1 contains the number 8 more than once"
+        );
+    }
+
+    #[test]
+    fn test_render_board() {
+        let (_, boards) = play::<2, 3>("5,12\n\n1 2 3\n4 5 6\n\n 7  8  9\n10 11 12\n").unwrap();
+
+        assert_eq!(boards[0].to_string(), " 1  2  3\n 4 [5] 6");
+        assert_eq!(boards[1].to_string(), "  7   8   9\n 10  11 [12]");
+    }
+
+    #[test]
+    fn test_inconsistent_row_width() {
+        let err = first_win_score::<2, 3>("1,2\n\n1 2 3\n4 5\n").unwrap_err();
+        let message = format!("{:#}", err);
+
+        assert!(
+            message.contains("row has 2 cells, but boards are 3 cells wide at line 4, column 1"),
+            "{}",
+            message
+        );
+    }
+}
@@ -1,7 +1,9 @@
-use std::collections::HashMap;
+use std::{array, cmp::Reverse, collections::HashMap, fmt};
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use gridly::prelude::*;
+use gridly_grids::{SparseGrid, VecGrid};
+use itertools::Itertools;
 use nom::{
     character::complete::{char, digit1, multispace0, multispace1, space0},
     IResult, Parser,
@@ -14,17 +16,31 @@ use nom_supreme::{
     ParserExt,
 };
 
+use crate::{grid::render_grid, library::Counter, Part, Solver};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Line {
     root: Location,
     vec: Vector,
 }
 
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let end = self.root + self.vec;
+
+        write!(
+            f,
+            "{},{} -> {},{}",
+            self.root.column.0, self.root.row.0, end.column.0, end.row.0
+        )
+    }
+}
+
 fn parse_location(input: &str) -> IResult<&str, Location, ErrorTree<&str>> {
     digit1
         .parse_from_str()
         .separated_array(char(','))
-        .map(|[row, column]| Row(row) + Column(column))
+        .map(|[x, y]| Row(y) + Column(x))
         .parse(input)
 }
 
@@ -46,33 +62,542 @@ fn final_parse_all_lines(input: &str) -> Result<Vec<Line>, ErrorTree<final_parse
     final_parser(parse_all_lines)(input)
 }
 
-fn solve(input: &str, filter: impl Fn(&Line) -> bool) -> anyhow::Result<usize> {
+/// Parse the lines, checking that each one is either straight or a 45 degree
+/// diagonal, since those are the only lines that can be walked one point at
+/// a time
+fn parse_lines(input: &str) -> anyhow::Result<Vec<Line>> {
     let lines = final_parse_all_lines(input).context("failed to parse lines")?;
 
-    let mut counts: HashMap<Location, usize> = HashMap::new();
+    if let Some((index, line)) = lines
+        .iter()
+        .enumerate()
+        .find(|(_, line)| !line.is_straight() && !line.is_diagonal())
+    {
+        bail!(
+            "line {} ({}) is neither straight nor a 45 degree diagonal",
+            index + 1,
+            line
+        );
+    }
+
+    Ok(lines)
+}
+
+/// A line segment in 3D space, between two inclusive endpoints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Line3 {
+    start: [isize; 3],
+    end: [isize; 3],
+}
+
+fn parse_point3(input: &str) -> IResult<&str, [isize; 3], ErrorTree<&str>> {
+    digit1
+        .parse_from_str()
+        .separated_array(char(','))
+        .parse(input)
+}
+
+fn parse_line3(input: &str) -> IResult<&str, Line3, ErrorTree<&str>> {
+    parse_point3
+        .separated_array(tag("->").delimited_by(space0))
+        .map(|[start, end]| Line3 { start, end })
+        .parse(input)
+}
+
+fn parse_all_lines3(input: &str) -> IResult<&str, Vec<Line3>, ErrorTree<&str>> {
+    collect_separated_terminated(parse_line3, multispace1, multispace0.all_consuming()).parse(input)
+}
+
+fn final_parse_all_lines3(input: &str) -> Result<Vec<Line3>, ErrorTree<final_parser::Location>> {
+    final_parser(parse_all_lines3)(input)
+}
+
+/// Parse 3D lines, checking that each one is either straight or a 45 degree
+/// diagonal
+fn parse_lines3(input: &str) -> anyhow::Result<Vec<Line3>> {
+    let lines = final_parse_all_lines3(input).context("failed to parse lines")?;
+
+    if let Some((index, line)) = lines
+        .iter()
+        .enumerate()
+        .find(|(_, line)| !line.is_diagonal())
+    {
+        bail!(
+            "line {} ({}) is neither straight nor a 45 degree diagonal",
+            index + 1,
+            line
+        );
+    }
+
+    Ok(lines)
+}
+
+/// Check if the input is made of 3D lines, based on how many coordinates the
+/// first point has
+fn is_3d(input: &str) -> bool {
+    input
+        .split("->")
+        .next()
+        .map(|point| point.trim().matches(',').count() == 2)
+        .unwrap_or(false)
+}
+
+impl fmt::Display for Line3 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} -> {}",
+            self.start.iter().join(","),
+            self.end.iter().join(",")
+        )
+    }
+}
+
+impl Line3 {
+    fn delta(&self) -> [isize; 3] {
+        array::from_fn(|axis| self.end[axis] - self.start[axis])
+    }
+
+    /// A line is straight if it runs along a single axis
+    fn is_straight(&self) -> bool {
+        self.delta().iter().filter(|&&delta| delta != 0).count() <= 1
+    }
+
+    /// A line is diagonal if it moves the same distance along every axis
+    /// that it moves along at all
+    fn is_diagonal(&self) -> bool {
+        self.delta()
+            .iter()
+            .map(|delta| delta.abs())
+            .filter(|&delta| delta != 0)
+            .all_equal()
+    }
+
+    /// Iterate over every point on this line, including both ends
+    fn points(&self) -> impl Iterator<Item = [isize; 3]> {
+        let delta = self.delta();
+        let unit = delta.map(isize::signum);
+        let magnitude = delta.iter().map(|delta| delta.abs()).max().unwrap_or(0) + 1;
+        let start = self.start;
+
+        (0..magnitude).map(move |i| array::from_fn(|axis| start[axis] + unit[axis] * i))
+    }
+}
+
+/// Draw every 3D line (only the straight ones, unless `diagonals`), counting
+/// how many lines cross each point
+fn draw3(input: &str, diagonals: bool) -> anyhow::Result<Counter<[isize; 3]>> {
+    let lines = parse_lines3(input)?;
+
+    Ok(lines
+        .iter()
+        .filter(|line| diagonals || line.is_straight())
+        .flat_map(|line| line.points())
+        .collect())
+}
+
+impl Line {
+    fn is_straight(&self) -> bool {
+        self.vec.direction().is_some()
+    }
+
+    fn is_diagonal(&self) -> bool {
+        self.vec.rows.0.abs() == self.vec.columns.0.abs()
+    }
 
+    /// Iterate over every point on this line, including both ends
+    fn points(&self) -> impl Iterator<Item = Location> {
+        let unit = Vector {
+            rows: self.vec.rows.clamp(Rows(-1), Rows(1)),
+            columns: self.vec.columns.clamp(Columns(-1), Columns(1)),
+        };
+
+        let magnitude = self.vec.rows.0.abs().max(self.vec.columns.0.abs()) + 1;
+        let root = self.root;
+
+        (0..magnitude).map(move |i| root + (unit * i))
+    }
+}
+
+/// Create an empty grid sized to fit every endpoint of the lines, so that it
+/// can be indexed directly
+fn grid_for<'a, T: Clone + PartialEq>(
+    lines: impl IntoIterator<Item = &'a Line>,
+    default: T,
+) -> SparseGrid<T> {
+    let endpoints = lines
+        .into_iter()
+        .flat_map(|line| [line.root, line.root + line.vec])
+        .collect_vec();
+
+    let rows = endpoints.iter().map(|location| location.row).minmax();
+    let columns = endpoints.iter().map(|location| location.column).minmax();
+
+    match (rows.into_option(), columns.into_option()) {
+        (Some((min_row, max_row)), Some((min_column, max_column))) => {
+            SparseGrid::new_rooted_default(
+                min_row + min_column,
+                (max_row - min_row + Rows(1)) + (max_column - min_column + Columns(1)),
+                default,
+            )
+        }
+        _ => SparseGrid::new_default(Vector::zero(), default),
+    }
+}
+
+/// A grid of overlap counts that lines can be drawn onto, so that drawing and
+/// counting are written once for both dense and sparse grids
+trait OverlapGrid: GridMut<Item = usize> {
+    /// Iterate over the counts of every point crossed by at least one line
+    fn counts(&self) -> impl Iterator<Item = usize> + '_;
+}
+
+impl OverlapGrid for SparseGrid<usize> {
+    fn counts(&self) -> impl Iterator<Item = usize> + '_ {
+        self.occupied_entries().map(|(_, &count)| count)
+    }
+}
+
+impl OverlapGrid for VecGrid<usize> {
+    fn counts(&self) -> impl Iterator<Item = usize> + '_ {
+        self.rows()
+            .iter()
+            .flat_map(|row| row.iter().copied())
+            .filter(|&count| count > 0)
+    }
+}
+
+/// Draw lines onto a grid, which must be large enough to contain them
+fn draw_onto<'a, G: OverlapGrid>(grid: &mut G, lines: impl IntoIterator<Item = &'a Line>) {
     lines
+        .into_iter()
+        .flat_map(|line| line.points())
+        .for_each(|location| *grid.get_mut(location).expect("grid is too small") += 1);
+}
+
+fn count_overlaps<G: OverlapGrid>(grid: &G, count_filter: impl Fn(usize) -> bool) -> usize {
+    grid.counts().filter(|&count| count_filter(count)).count()
+}
+
+/// Inputs whose bounding box (from the origin, since coordinates are never
+/// negative) has fewer points than this are drawn onto a dense `VecGrid`
+/// instead of a `SparseGrid`
+const DENSE_AREA_LIMIT: isize = 1 << 20;
+
+fn parse_filtered_lines(input: &str, diagonals: bool) -> anyhow::Result<Vec<Line>> {
+    let mut lines = parse_lines(input)?;
+    lines.retain(|line| diagonals || line.is_straight());
+    Ok(lines)
+}
+
+/// Draw every line (only the straight ones, unless `diagonals`) onto a grid,
+/// counting how many lines cross each point
+fn draw(input: &str, diagonals: bool) -> anyhow::Result<SparseGrid<usize>> {
+    let lines = parse_filtered_lines(input, diagonals)?;
+
+    let mut grid = grid_for(&lines, 0);
+    draw_onto(&mut grid, &lines);
+
+    Ok(grid)
+}
+
+/// Draw every 2D line, then count the points whose number of overlapping
+/// lines is accepted by `count_filter`. Small inputs use a dense grid, and
+/// large or spread out ones use a sparse grid.
+fn solve_2d(
+    input: &str,
+    diagonals: bool,
+    count_filter: impl Fn(usize) -> bool,
+) -> anyhow::Result<usize> {
+    let lines = parse_filtered_lines(input, diagonals)?;
+
+    let extent = lines
         .iter()
-        .filter(|&line| filter(line))
-        .flat_map(|line| {
-            let unit = Vector {
-                rows: line.vec.rows.clamp(Rows(-1), Rows(1)),
-                columns: line.vec.columns.clamp(Columns(-1), Columns(1)),
-            };
+        .flat_map(|line| [line.root, line.root + line.vec])
+        .fold(Vector::zero(), |extent, location| Vector {
+            rows: extent.rows.max(Rows(location.row.0 + 1)),
+            columns: extent.columns.max(Columns(location.column.0 + 1)),
+        });
 
-            let magnitude = line.vec.rows.0.abs().max(line.vec.columns.0.abs()) + 1;
+    // An area too large to even compute is certainly too large for a dense grid
+    let is_dense = extent
+        .rows
+        .0
+        .checked_mul(extent.columns.0)
+        .is_some_and(|area| area < DENSE_AREA_LIMIT);
 
-            (0..magnitude).map(move |i| line.root + (unit * i))
-        })
-        .for_each(|loc| *counts.entry(loc).or_default() += 1);
+    if is_dense {
+        let mut grid = VecGrid::new(extent).context("grid is too large")?;
+        draw_onto(&mut grid, &lines);
+        Ok(count_overlaps(&grid, count_filter))
+    } else {
+        let mut grid = grid_for(&lines, 0);
+        draw_onto(&mut grid, &lines);
+        Ok(count_overlaps(&grid, count_filter))
+    }
+}
+
+/// Draw every line (only the straight ones, unless `diagonals`), then count
+/// the points whose number of overlapping lines is accepted by `count_filter`.
+/// The input may be either 2D or 3D lines.
+fn solve(
+    input: &str,
+    diagonals: bool,
+    count_filter: impl Fn(usize) -> bool,
+) -> anyhow::Result<usize> {
+    if is_3d(input) {
+        let counts = draw3(input, diagonals)?;
+
+        Ok(counts
+            .iter_counts()
+            .filter(|&(_, count)| count_filter(count))
+            .count())
+    } else {
+        solve_2d(input, diagonals, count_filter)
+    }
+}
+
+/// The number of lines crossing a point, with and without the diagonals
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct Overlaps {
+    straight: usize,
+    all: usize,
+}
 
-    Ok(counts.values().filter(|&&count| count > 1).count())
+impl Overlaps {
+    fn add(&mut self, straight: bool) {
+        self.straight += straight as usize;
+        self.all += 1;
+    }
+}
+
+/// Count the points crossed by more than one line, both for the straight
+/// lines alone and for all lines, drawing every line only once
+fn solve_both(input: &str) -> anyhow::Result<(usize, usize)> {
+    let overlaps: Vec<Overlaps> = if is_3d(input) {
+        let mut overlaps: HashMap<[isize; 3], Overlaps> = HashMap::new();
+
+        for line in parse_lines3(input)? {
+            let straight = line.is_straight();
+            line.points()
+                .for_each(|point| overlaps.entry(point).or_default().add(straight));
+        }
+
+        overlaps.into_values().collect()
+    } else {
+        let lines = parse_lines(input)?;
+        let mut grid = grid_for(&lines, Overlaps::default());
+
+        for line in &lines {
+            let straight = line.is_straight();
+            line.points()
+                .for_each(|location| grid[location].add(straight));
+        }
+
+        grid.occupied_entries()
+            .map(|(_, &overlaps)| overlaps)
+            .collect()
+    };
+
+    Ok((
+        overlaps
+            .iter()
+            .filter(|overlaps| overlaps.straight > 1)
+            .count(),
+        overlaps.iter().filter(|overlaps| overlaps.all > 1).count(),
+    ))
+}
+
+/// Find the point crossed by the most lines, along with how many lines cross
+/// it. Ties are broken by lowest row, then lowest column.
+fn max_overlap(grid: &SparseGrid<usize>) -> Option<(Location, usize)> {
+    grid.occupied_entries()
+        .min_by_key(|&(location, &count)| (Reverse(count), location.row, location.column))
+        .map(|(&location, &count)| (location, count))
+}
+
+/// Render the overlap counts of a drawn grid, with `*` for 10 or more
+fn render_heatmap(grid: &SparseGrid<usize>) -> String {
+    render_grid(grid, |&count| match count {
+        0 => '.',
+        count => char::from_digit(count as u32, 10).unwrap_or('*'),
+    })
 }
 
 pub fn part1(input: &str) -> anyhow::Result<usize> {
-    solve(input, |line| line.vec.direction().is_some())
+    solve(input, false, |count| count > 1)
 }
 
 pub fn part2(input: &str) -> anyhow::Result<usize> {
-    solve(input, |_| true)
+    solve(input, true, |count| count > 1)
+}
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
+
+    fn both(&self, input: &str) -> anyhow::Result<(String, String)> {
+        let (part1, part2) = solve_both(input)?;
+        Ok((part1.to_string(), part2.to_string()))
+    }
+
+    fn details(&self, part: Part, input: &str) -> anyhow::Result<Option<String>> {
+        // There's no heatmap for 3D lines
+        if is_3d(input) {
+            return Ok(None);
+        }
+
+        let grid = draw(input, part == Part::Part2)?;
+        let mut details = render_heatmap(&grid);
+
+        if let Some((location, count)) = max_overlap(&grid) {
+            details.push_str(&format!(
+                "\nmost crossed point: {},{} with {} lines",
+                location.column.0, location.row.0, count
+            ));
+        }
+
+        Ok(Some(details))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+0,9 -> 5,9
+8,0 -> 0,8
+9,4 -> 3,4
+2,2 -> 2,1
+7,0 -> 7,4
+6,4 -> 2,0
+0,9 -> 2,9
+3,4 -> 1,4
+0,0 -> 8,8
+5,5 -> 8,2
+";
+
+    #[test]
+    fn test_sample() {
+        assert_eq!(part1(SAMPLE).unwrap(), 5);
+        assert_eq!(part2(SAMPLE).unwrap(), 12);
+    }
+
+    #[test]
+    fn test_render_heatmap() {
+        let grid = draw(SAMPLE, true).unwrap();
+
+        assert_eq!(grid[(4, 4)], 3);
+        assert_eq!(grid[(4, 6)], 3);
+        assert_eq!(
+            render_heatmap(&grid),
+            "\
+1.1....11.
+.111...2..
+..2.1.111.
+...1.2.2..
+.112313211
+...1.2....
+..1...1...
+.1.....1..
+1.......1.
+222111...."
+        );
+    }
+
+    #[test]
+    fn test_exact_overlaps() {
+        assert_eq!(solve(SAMPLE, true, |count| count == 2).unwrap(), 10);
+        assert_eq!(solve(SAMPLE, true, |count| count == 3).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_3d_lines() {
+        // The diagonal crosses the first line at 1,1,1 and the second at 2,2,2
+        let input = "0,0,0 -> 3,3,3\n1,1,1 -> 1,1,5\n0,2,2 -> 4,2,2\n";
+
+        assert_eq!(part1(input).unwrap(), 0);
+        assert_eq!(part2(input).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_illegal_slope() {
+        let err = part2("0,9 -> 5,9\n0,0 -> 2,5\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 2 (0,0 -> 2,5) is neither straight nor a 45 degree diagonal"
+        );
+
+        let err = part2("0,0,0 -> 2,2,3\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1 (0,0,0 -> 2,2,3) is neither straight nor a 45 degree diagonal"
+        );
+    }
+
+    #[test]
+    fn test_solve_both() {
+        assert_eq!(
+            solve_both(SAMPLE).unwrap(),
+            (part1(SAMPLE).unwrap(), part2(SAMPLE).unwrap())
+        );
+
+        let input = "0,0,0 -> 3,3,3\n1,1,1 -> 1,1,5\n0,2,2 -> 4,2,2\n1,1,0 -> 1,1,2\n";
+        assert_eq!(
+            solve_both(input).unwrap(),
+            (part1(input).unwrap(), part2(input).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_max_overlap() {
+        let grid = draw(SAMPLE, true).unwrap();
+        assert_eq!(max_overlap(&grid), Some((Location::new(4, 4), 3)));
+
+        let grid = draw(SAMPLE, false).unwrap();
+        assert_eq!(max_overlap(&grid).map(|(_, count)| count), Some(2));
+    }
+
+    #[test]
+    fn test_dense_matches_sparse() {
+        for diagonals in [false, true] {
+            let lines = parse_filtered_lines(SAMPLE, diagonals).unwrap();
+
+            let mut dense: VecGrid<usize> = VecGrid::new((10, 10)).unwrap();
+            draw_onto(&mut dense, &lines);
+
+            let mut sparse = grid_for(&lines, 0);
+            draw_onto(&mut sparse, &lines);
+
+            assert_eq!(
+                count_overlaps(&dense, |count| count > 1),
+                count_overlaps(&sparse, |count| count > 1),
+            );
+            assert_eq!(
+                dense.counts().collect::<Counter<usize>>(),
+                sparse.counts().collect::<Counter<usize>>(),
+            );
+        }
+    }
+
+    #[test]
+    fn test_huge_extent_is_sparse() {
+        // The bounding box area overflows an isize, so this has to be drawn
+        // onto a sparse grid
+        let input = "\
+0,0 -> 0,1
+4000000000,4000000000 -> 4000000000,4000000001
+4000000000,4000000001 -> 4000000000,4000000002
+";
+
+        assert_eq!(part1(input).unwrap(), 1);
+    }
 }
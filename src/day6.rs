@@ -1,44 +1,323 @@
-use std::collections::HashMap;
+use std::mem;
 
-use anyhow::Context;
+use anyhow::{ensure, Context};
 use itertools::Itertools;
 
+use crate::{library::parse_input_iter, Part, Solver};
+
+/// A fish spawns a new fish every `RESET_CYCLE` days
+const RESET_CYCLE: usize = 7;
+
+/// A newborn fish spawns for the first time after `MATURATION_DELAY` days
+const MATURATION_DELAY: usize = 9;
+
+/// The number of fish with each timer value, used as a ring buffer
 struct FishCounter {
-    population: HashMap<i32, i64>,
+    population: Vec<u128>,
+    reset_cycle: usize,
+    maturation_delay: usize,
 }
 
-impl FromIterator<i32> for FishCounter {
-    fn from_iter<T: IntoIterator<Item = i32>>(iter: T) -> Self {
-        let mut population = HashMap::new();
-        iter.into_iter()
-            .for_each(|item| *population.entry(item).or_default() += 1);
-        Self { population }
+impl FishCounter {
+    fn new(input: &str, reset_cycle: usize, maturation_delay: usize) -> anyhow::Result<Self> {
+        ensure!(reset_cycle > 0, "the reset cycle must be at least 1 day");
+        ensure!(
+            maturation_delay >= reset_cycle,
+            "the maturation delay ({}) must be at least the reset cycle ({})",
+            maturation_delay,
+            reset_cycle
+        );
+
+        // Timers may be separated by commas, whitespace, or both
+        let timers: Vec<usize> = parse_input_iter(
+            input
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|token| !token.is_empty()),
+        )
+        .context("failed to parse timers")?;
+
+        ensure!(!timers.is_empty(), "no fish in input");
+
+        // Every timer is less than the maturation delay, except possibly for
+        // the initial ones
+        let len = timers
+            .iter()
+            .map(|&timer| timer + 1)
+            .fold(maturation_delay, usize::max);
+
+        let mut population = vec![0; len];
+        timers.iter().for_each(|&timer| population[timer] += 1);
+
+        Ok(Self {
+            population,
+            reset_cycle,
+            maturation_delay,
+        })
+    }
+
+    /// Simulate a single day. Rotating the buffer counts every timer down;
+    /// the spawning fish are taken out first and added back afterwards.
+    /// Returns `None` if the population overflows.
+    fn step(&mut self) -> Option<()> {
+        let spawning = mem::take(&mut self.population[0]);
+        self.population.rotate_left(1);
+
+        // The fish create new fish
+        let newborns = &mut self.population[self.maturation_delay - 1];
+        *newborns = newborns.checked_add(spawning)?;
+
+        // Then they get sleepy
+        let parents = &mut self.population[self.reset_cycle - 1];
+        *parents = parents.checked_add(spawning)?;
+
+        Some(())
+    }
+
+    fn total(&self) -> Option<u128> {
+        self.population
+            .iter()
+            .try_fold(0u128, |total, &count| total.checked_add(count))
     }
 }
 
-pub fn solve(input: &str, days: i32) -> anyhow::Result<i64> {
-    let mut counter: FishCounter = input
-        .split(',')
-        .map(|day| day.parse().context("failed to parse day"))
-        .try_collect()?;
+/// Simulate a population of fish with the given spawning biology, returning
+/// the number of fish with each timer value after `days` days
+pub fn histogram_with(
+    input: &str,
+    days: i32,
+    reset_cycle: usize,
+    maturation_delay: usize,
+) -> anyhow::Result<Vec<u128>> {
+    let mut counter = FishCounter::new(input, reset_cycle, maturation_delay)?;
 
     for day in 0..days {
-        if let Some(day_count) = counter.population.remove(&day) {
-            // The fish create new fish
-            *counter.population.entry(day + 9).or_default() += day_count;
+        counter
+            .step()
+            .with_context(|| format!("population overflowed on day {}", day))?;
+    }
+
+    Ok(counter.population)
+}
+
+/// Simulate a population of fish with the given spawning biology, returning
+/// the total population after `days` days
+pub fn solve_with(
+    input: &str,
+    days: i32,
+    reset_cycle: usize,
+    maturation_delay: usize,
+) -> anyhow::Result<u128> {
+    histogram_with(input, days, reset_cycle, maturation_delay)?
+        .iter()
+        .try_fold(0u128, |total, &count| total.checked_add(count))
+        .context("population overflowed")
+}
 
-            // Then they get sleepy
-            *counter.population.entry(day + 7).or_default() += day_count;
+/// Simulate the population, returning the total population at the end of each
+/// day, starting with the initial population, for days `0..=days`
+pub fn daily_totals(input: &str, days: i32) -> anyhow::Result<Vec<u128>> {
+    let mut counter = FishCounter::new(input, RESET_CYCLE, MATURATION_DELAY)?;
+    let mut totals = Vec::with_capacity(days.max(0) as usize + 1);
+
+    for day in 0..=days {
+        if day > 0 {
+            counter
+                .step()
+                .with_context(|| format!("population overflowed on day {}", day))?;
         }
+
+        totals.push(counter.total().context("population overflowed")?);
     }
 
-    Ok(counter.population.values().copied().sum())
+    Ok(totals)
 }
 
-pub fn part1(input: &str) -> anyhow::Result<i64> {
+pub fn solve(input: &str, days: i32) -> anyhow::Result<u128> {
+    solve_with(input, days, RESET_CYCLE, MATURATION_DELAY)
+}
+
+pub fn part1(input: &str) -> anyhow::Result<u128> {
     solve(input, 80)
 }
 
-pub fn part2(input: &str) -> anyhow::Result<i64> {
+pub fn part2(input: &str) -> anyhow::Result<u128> {
     solve(input, 256)
 }
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
+
+    fn details(&self, part: Part, input: &str) -> anyhow::Result<Option<String>> {
+        let days = match part {
+            Part::Part1 => 80,
+            Part::Part2 => 256,
+        };
+
+        let totals = daily_totals(input, days)?;
+
+        Ok(Some(
+            totals
+                .iter()
+                .enumerate()
+                .map(|(day, total)| format!("day {}: {}", day, total))
+                .join("\n"),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    const SAMPLE: &str = "3,4,3,1,2";
+
+    /// The original implementation, which tracks the population by the
+    /// absolute day that each group of fish spawns on
+    fn solve_with_hashmap(input: &str, days: i32) -> u128 {
+        let mut population: HashMap<i32, u128> = input
+            .split(',')
+            .map(|day| day.parse().unwrap())
+            .counts()
+            .into_iter()
+            .map(|(day, count)| (day, count as u128))
+            .collect();
+
+        for day in 0..days {
+            if let Some(day_count) = population.remove(&day) {
+                *population.entry(day + 9).or_default() += day_count;
+                *population.entry(day + 7).or_default() += day_count;
+            }
+        }
+
+        population.values().copied().sum()
+    }
+
+    /// Simulate every fish individually
+    fn simulate_naive(
+        timers: &[usize],
+        days: usize,
+        reset_cycle: usize,
+        maturation_delay: usize,
+    ) -> usize {
+        let mut timers = timers.to_vec();
+
+        for _ in 0..days {
+            let mut newborns = 0;
+
+            for timer in &mut timers {
+                match *timer {
+                    0 => {
+                        *timer = reset_cycle - 1;
+                        newborns += 1;
+                    }
+                    _ => *timer -= 1,
+                }
+            }
+
+            timers.extend(std::iter::repeat_n(maturation_delay - 1, newborns));
+        }
+
+        timers.len()
+    }
+
+    #[test]
+    fn test_sample() {
+        assert_eq!(solve(SAMPLE, 18).unwrap(), 26);
+        assert_eq!(part1(SAMPLE).unwrap(), 5934);
+        assert_eq!(part2(SAMPLE).unwrap(), 26984457539);
+    }
+
+    #[test]
+    fn test_separators() {
+        let expected = part1(SAMPLE).unwrap();
+
+        for input in [
+            "3,4,3,1,2\n",
+            "  3,4,3,1,2  ",
+            "3 4 3 1 2",
+            "3\n4\n3\n1\n2\n",
+            "3, 4, 3,\r\n1, 2",
+        ] {
+            assert_eq!(part1(input).unwrap(), expected, "input: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_invalid_timer() {
+        let err = part1("3,4,x,1,2").unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "failed to parse timers: failed to parse token \"x\" at index 2: \
+            invalid digit found in string"
+        );
+
+        assert!(part1(" \n").is_err());
+    }
+
+    #[test]
+    fn test_histogram() {
+        let histogram = histogram_with(SAMPLE, 18, RESET_CYCLE, MATURATION_DELAY).unwrap();
+        assert_eq!(histogram, [3, 5, 3, 2, 2, 1, 5, 1, 4]);
+    }
+
+    #[test]
+    fn test_daily_totals() {
+        let totals = daily_totals(SAMPLE, 18).unwrap();
+
+        assert_eq!(totals.len(), 19);
+        assert_eq!(totals[..6], [5, 5, 6, 7, 9, 10]);
+        assert_eq!(totals.last().copied(), Some(solve(SAMPLE, 18).unwrap()));
+    }
+
+    #[test]
+    fn test_beyond_i64() {
+        let population = solve(SAMPLE, 512).unwrap();
+
+        assert!(population > i64::MAX as u128);
+        assert_eq!(population, 130644480977473980844);
+    }
+
+    #[test]
+    fn test_overflow_is_an_error() {
+        assert!(solve(SAMPLE, 2000).is_err());
+    }
+
+    #[test]
+    fn test_ring_buffer_matches_hashmap() {
+        for days in [0, 1, 7, 18, 80, 150, 256] {
+            assert_eq!(
+                solve(SAMPLE, days).unwrap(),
+                solve_with_hashmap(SAMPLE, days),
+                "after {} days",
+                days
+            );
+        }
+    }
+
+    #[test]
+    fn test_shortened_cycle() {
+        for days in [0, 1, 5, 10, 20] {
+            assert_eq!(
+                solve_with(SAMPLE, days as i32, 3, 4).unwrap() as usize,
+                simulate_naive(&[3, 4, 3, 1, 2], days, 3, 4),
+                "after {} days",
+                days
+            );
+        }
+    }
+
+    #[test]
+    fn test_maturation_shorter_than_reset() {
+        assert!(solve_with(SAMPLE, 10, 5, 4).is_err());
+    }
+}
@@ -1,191 +1,591 @@
-use std::collections::{btree_map::Entry, BTreeMap};
+use std::{collections::BTreeMap, fmt, num::ParseIntError, ops::RangeInclusive, str::FromStr};
 
-use anyhow::Context;
+use anyhow::{ensure, Context};
+use rayon::prelude::*;
+use thiserror::Error;
 
-use crate::library::parse_input_iter;
+use crate::{library::parse_input_iter, Part, Solver};
+
+/// Some number of crabs at the same position. In the input, this is either a
+/// bare position, for a single crab, or a `position:count` pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrabGroup {
+    pub position: i32,
+    pub count: i64,
+}
+
+#[derive(Debug, Error)]
+pub enum CrabGroupError {
+    #[error("invalid position")]
+    InvalidPosition(#[source] ParseIntError),
+
+    #[error("invalid count")]
+    InvalidCount(#[source] ParseIntError),
+
+    #[error("count must be at least 1")]
+    EmptyGroup,
+}
+
+impl FromStr for CrabGroup {
+    type Err = CrabGroupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (position, count) = match s.split_once(':') {
+            Some((position, count)) => (
+                position,
+                count.parse().map_err(CrabGroupError::InvalidCount)?,
+            ),
+            None => (s, 1),
+        };
+
+        let position = position.parse().map_err(CrabGroupError::InvalidPosition)?;
+
+        match count {
+            count if count < 1 => Err(CrabGroupError::EmptyGroup),
+            count => Ok(CrabGroup { position, count }),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
-struct CrabList {
-    crab_counts: BTreeMap<i32, i32>,
+pub struct CrabList {
+    crab_counts: BTreeMap<i32, i64>,
 }
 
 impl FromIterator<i32> for CrabList {
     fn from_iter<T: IntoIterator<Item = i32>>(iter: T) -> Self {
         let mut crab_counts = BTreeMap::new();
         iter.into_iter()
-            .for_each(|item| *crab_counts.entry(item).or_default() += 1);
+            .for_each(|position| *crab_counts.entry(position).or_default() += 1);
         Self { crab_counts }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-struct FormationFlank {
-    position: i32,
-    count: i32,
+/// A position for the crabs to align on, and the total fuel they spend to get
+/// there
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alignment {
+    pub position: i32,
+    pub fuel: i64,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-enum Outcome {
-    Done,
-    NotDone,
+impl fmt::Display for Alignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "align at {} using {} fuel", self.position, self.fuel)
+    }
 }
 
-impl FormationFlank {
-    // Send the crabs to a new position, update the count, and return the
-    // expended fuel
-    fn advance_to(&mut self, target: FormationFlank) -> i32 {
-        let distance = (self.position - target.position).abs();
-        let fuel = distance * self.count;
-
-        self.position = target.position;
-        self.count += target.count;
+impl Alignment {
+    /// Create an alignment, checking that its total fuel fits in an `i64`
+    fn new(position: i32, fuel: i128) -> anyhow::Result<Self> {
+        let fuel = fuel
+            .try_into()
+            .with_context(|| format!("aligning at {} takes too much fuel", position))?;
 
-        fuel
+        Ok(Alignment { position, fuel })
     }
 }
 
-pub fn part1(input: &str) -> anyhow::Result<i32> {
-    let crabs: CrabList = parse_input_iter(input.split(',')).context("failed to parse input")?;
+impl CrabList {
+    /// Gather groups of crabs into a list, adding up the counts of groups at
+    /// the same position
+    fn from_groups(groups: impl IntoIterator<Item = CrabGroup>) -> anyhow::Result<Self> {
+        let mut crab_counts = BTreeMap::new();
+
+        for group in groups {
+            let count: &mut i64 = crab_counts.entry(group.position).or_default();
+            *count = count
+                .checked_add(group.count)
+                .with_context(|| format!("too many crabs at position {}", group.position))?;
+        }
+
+        Ok(Self { crab_counts })
+    }
+
+    /// The total fuel spent by all the crabs to move to `target`. This is
+    /// added up as an `i128`, so that the searches can compare positions
+    /// whose total is too large to be an answer.
+    fn fuel_to(&self, target: i32, cost: impl Fn(i64) -> i64) -> anyhow::Result<i128> {
+        self.crab_counts
+            .iter()
+            .try_fold(0i128, |total, (&position, &count)| {
+                let distance = (position as i64 - target as i64).abs();
+
+                (count as i128)
+                    .checked_mul(cost(distance) as i128)
+                    .and_then(|fuel| total.checked_add(fuel))
+            })
+            .with_context(|| format!("total fuel to align at {} overflowed", target))
+    }
 
-    let mut fuel = 0;
+    fn align_at(&self, position: i32, cost: impl Fn(i64) -> i64) -> anyhow::Result<Alignment> {
+        self.fuel_to(position, cost)
+            .and_then(|fuel| Alignment::new(position, fuel))
+    }
 
-    let mut crab_iter = crabs
-        .crab_counts
-        .range(..)
-        .map(|(&position, &count)| FormationFlank { position, count });
+    /// The position of the middle crab. With an even number of crabs, this is
+    /// the lower of the two middle crabs; every position between them costs
+    /// the same linear fuel, so it doesn't matter which is picked.
+    fn median(&self) -> anyhow::Result<Option<i32>> {
+        let total = self
+            .crab_counts
+            .values()
+            .try_fold(0i64, |total, &count| total.checked_add(count))
+            .context("there are too many crabs to find the middle one")?;
+        let mut seen = 0;
+
+        Ok(self.crab_counts.iter().find_map(|(&position, &count)| {
+            seen += count;
+            (seen >= total - seen).then_some(position)
+        }))
+    }
+}
 
-    let mut left_flank = crab_iter.next().context("There are no crabs 🙁")?;
+/// Once the ternary search has narrowed the candidates down to this many
+/// positions, just try all of them
+const SCAN_WIDTH: i64 = 256;
+
+/// Try aligning the crabs at every position in `targets`, in parallel, and
+/// return the cheapest one. Ties go to the leftmost position. This works for
+/// any cost function, at the price of evaluating every candidate.
+pub fn scan_alignment(
+    crabs: &CrabList,
+    targets: RangeInclusive<i32>,
+    cost: impl Fn(i64) -> i64 + Sync,
+) -> anyhow::Result<Option<Alignment>> {
+    let best = targets
+        .into_par_iter()
+        .map(|position| {
+            crabs
+                .fuel_to(position, &cost)
+                .map(|fuel| Some((fuel, position)))
+        })
+        .try_reduce(|| None, |a, b| Ok(a.into_iter().chain(b).min()))?;
+
+    best.map(|(fuel, position)| Alignment::new(position, fuel))
+        .transpose()
+}
 
-    let mut right_flank = match crab_iter.next_back() {
-        Some(flank) => flank,
-        None => return Ok(fuel),
+/// Find the position where the crabs can align spending the least total fuel,
+/// given the fuel it costs a single crab to move a given distance. Returns
+/// `None` if there are no crabs.
+///
+/// `cost` must be convex and nondecreasing, which makes the total fuel convex
+/// in the target position. This lets us ternary search for the minimum: each
+/// step compares a target with its right neighbor, and the minimum is on
+/// whichever side the fuel is going downhill. The last few candidates are
+/// scanned with `scan_alignment`.
+pub fn best_alignment(
+    crabs: &CrabList,
+    cost: impl Fn(i64) -> i64 + Sync,
+) -> anyhow::Result<Option<Alignment>> {
+    let (mut low, mut high) = match (
+        crabs.crab_counts.first_key_value(),
+        crabs.crab_counts.last_key_value(),
+    ) {
+        (Some((&min, _)), Some((&max, _))) => (min, max),
+        _ => return Ok(None),
     };
 
-    loop {
-        // Figure out which side is moving. The side with fewer 🦀🦀🦀🦀 needs
-        // to move towards the center. Suppose it is the left flank, the
-        // logic works the same either way. The left flank needs to move towars
-        // a target flank; this is either the next leftmost flank or the right
-        // flank; in the latter case, this will be the final move.
-        let (mobile_flank, target_flank, outcome) = match match left_flank.count
-            <= right_flank.count
-        {
-            // The left side will be moving
-            true => (&mut left_flank, right_flank, crab_iter.next()),
-
-            // The right side will be moving
-            false => (&mut right_flank, left_flank, crab_iter.next_back()),
-        } {
-            // The mobile side will reach some inner destination
-            (mobile_flank, _, Some(next_flank)) => (mobile_flank, next_flank, Outcome::NotDone),
-
-            // The mobile side will reach the opposite end; this is the final move
-            // The crabs move and are all together now 🦀🦀🦀🦀
-            (mobile_flank, immobile_flank, None) => (mobile_flank, immobile_flank, Outcome::Done),
-        };
+    // The crabs can be further apart than fits in an i32
+    let width = |low: i32, high: i32| high as i64 - low as i64;
 
-        let spent = mobile_flank.advance_to(target_flank);
-        fuel += spent;
-        if outcome == Outcome::Done {
-            break Ok(fuel);
+    while width(low, high) > SCAN_WIDTH {
+        let mid = low + (width(low, high) / 2) as i32;
+
+        if crabs.fuel_to(mid, &cost)? <= crabs.fuel_to(mid + 1, &cost)? {
+            high = mid;
+        } else {
+            low = mid + 1;
         }
     }
+
+    scan_alignment(crabs, low..=high, cost)
 }
 
-#[derive(Debug, Clone, Copy)]
-struct CrabCohort {
-    count: i32,
-    fuel_cost: i32,
+/// Find the position where the crabs can align spending the least total fuel,
+/// with linear fuel costs. This is always the median position.
+pub fn median_alignment(crabs: &CrabList) -> anyhow::Result<Option<Alignment>> {
+    crabs
+        .median()?
+        .map(|median| crabs.align_at(median, linear_cost))
+        .transpose()
 }
 
-impl CrabCohort {
-    fn step(&mut self) -> i32 {
-        let step_cost = self.fuel_cost * self.count;
-        self.fuel_cost += 1;
-        step_cost
-    }
-    fn step_predict(&self) -> i32 {
-        self.fuel_cost * self.count
+/// Each step costs 1 fuel
+pub fn linear_cost(distance: i64) -> i64 {
+    distance
+}
+
+/// Each step costs 1 more fuel than the previous one
+pub fn triangular_cost(distance: i64) -> i64 {
+    // Halve whichever factor is even before multiplying, so that this can't
+    // overflow for any distance between two `i32` positions
+    match distance % 2 {
+        0 => distance / 2 * (distance + 1),
+        _ => (distance + 1) / 2 * distance,
     }
 }
 
-#[derive(Debug, Clone)]
-struct CrabCahoot {
-    cohorts: Vec<CrabCohort>,
+/// Parse the crab positions, ensuring that there's at least one crab
+fn parse_crabs(input: &str) -> anyhow::Result<CrabList> {
+    let input = input.trim();
+    ensure!(!input.is_empty(), "There are no crabs 🙁");
+
+    let groups: Vec<CrabGroup> =
+        parse_input_iter(input.split(',')).context("failed to parse input")?;
+    CrabList::from_groups(groups)
 }
 
-impl CrabCahoot {
-    fn step(&mut self) -> i32 {
-        self.cohorts.iter_mut().map(|cohort| cohort.step()).sum()
+fn solve(
+    input: &str,
+    align: impl Fn(&CrabList) -> anyhow::Result<Option<Alignment>>,
+) -> anyhow::Result<Alignment> {
+    let crabs = parse_crabs(input)?;
+    align(&crabs)?.context("There are no crabs 🙁")
+}
+
+pub fn solve_part1(input: &str) -> anyhow::Result<Alignment> {
+    solve(input, median_alignment)
+}
+
+pub fn solve_part2(input: &str) -> anyhow::Result<Alignment> {
+    solve(input, |crabs| best_alignment(crabs, triangular_cost))
+}
+
+pub fn part1(input: &str) -> anyhow::Result<i64> {
+    solve_part1(input).map(|alignment| alignment.fuel)
+}
+
+pub fn part2(input: &str) -> anyhow::Result<i64> {
+    solve_part2(input).map(|alignment| alignment.fuel)
+}
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
     }
-    fn step_predict(&self) -> i32 {
-        self.cohorts
-            .iter()
-            .map(|cohort| cohort.step_predict())
-            .sum()
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
     }
-    fn merge(&mut self, cahoot: CrabCahoot) {
-        self.cohorts.extend(cahoot.cohorts)
+
+    fn details(&self, part: Part, input: &str) -> anyhow::Result<Option<String>> {
+        let alignment = match part {
+            Part::Part1 => solve_part1(input)?,
+            Part::Part2 => solve_part2(input)?,
+        };
+
+        Ok(Some(alignment.to_string()))
     }
 }
 
-#[derive(Debug, Clone)]
-struct CrabPopulation {
-    population: BTreeMap<i32, CrabCahoot>,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// Find the least total fuel by trying every position between the
+    /// outermost crabs. This works for any cost function, so it's used to
+    /// check the faster searches.
+    fn brute_force(crabs: &CrabList, cost: impl Fn(i64) -> i64) -> Option<i64> {
+        let (&min, _) = crabs.crab_counts.first_key_value()?;
+        let (&max, _) = crabs.crab_counts.last_key_value()?;
+
+        (min..=max)
+            .map(|target| crabs.fuel_to(target, &cost).unwrap())
+            .min()
+            .map(|fuel| fuel.try_into().unwrap())
+    }
 
-impl FromIterator<i32> for CrabPopulation {
-    fn from_iter<T: IntoIterator<Item = i32>>(iter: T) -> Self {
-        let mut population: BTreeMap<i32, CrabCahoot> = BTreeMap::new();
-        iter.into_iter().for_each(|position| {
-            population
-                .entry(position)
-                .and_modify(|cahoot| cahoot.cohorts[0].count += 1)
-                .or_insert(CrabCahoot {
-                    cohorts: vec![CrabCohort {
-                        count: 1,
-                        fuel_cost: 1,
-                    }],
-                });
-        });
-        Self { population }
-    }
-}
-
-pub fn part2(input: &str) -> anyhow::Result<i32> {
-    let mut crabs: CrabPopulation =
-        parse_input_iter(input.split(',')).context("failed to parse input")?;
+    const SAMPLE: &str = "16,1,2,0,4,2,7,1,2,14";
 
-    let mut fuel = 0;
+    fn crabs(input: &str) -> CrabList {
+        parse_crabs(input).unwrap()
+    }
 
-    loop {
-        let mut range = crabs.population.range(..);
+    #[test]
+    fn test_linear_cost() {
+        assert_eq!(
+            median_alignment(&crabs(SAMPLE)).unwrap().map(|a| a.fuel),
+            Some(37)
+        );
+        assert_eq!(
+            best_alignment(&crabs(SAMPLE), linear_cost)
+                .unwrap()
+                .map(|a| a.fuel),
+            Some(37)
+        );
+        assert_eq!(part1(SAMPLE).unwrap(), 37);
+    }
 
-        let (&left_flank, left_cahoot) = range.next().context("there were no crabs :(")?;
+    #[test]
+    fn test_triangular_cost() {
+        assert_eq!(
+            best_alignment(&crabs(SAMPLE), triangular_cost)
+                .unwrap()
+                .map(|a| a.fuel),
+            Some(168)
+        );
+        assert_eq!(part2(SAMPLE).unwrap(), 168);
+    }
 
-        let (&right_flank, right_cahoot) = match range.next_back() {
-            Some(entry) => entry,
-            None => break Ok(fuel),
-        };
+    #[test]
+    fn test_alignment_position() {
+        assert_eq!(
+            solve_part1(SAMPLE).unwrap(),
+            Alignment {
+                position: 2,
+                fuel: 37
+            }
+        );
+        assert_eq!(
+            solve_part2(SAMPLE).unwrap(),
+            Alignment {
+                position: 5,
+                fuel: 168
+            }
+        );
+    }
 
-        let (origin, destination) = if left_cahoot.step_predict() <= right_cahoot.step_predict() {
-            (left_flank, left_flank + 1)
-        } else {
-            (right_flank, right_flank - 1)
+    #[test]
+    fn test_custom_cost() {
+        // With a flat cost per crab that moves, the best position is the one
+        // with the most crabs already on it. This isn't convex, so it needs
+        // the exhaustive search.
+        assert_eq!(
+            brute_force(&crabs(SAMPLE), |distance| (distance > 0) as i64),
+            Some(7)
+        );
+    }
+
+    #[test]
+    fn test_even_median() {
+        // Any position from 2 to 8 is optimal
+        let spread = crabs("0,2,8,10");
+        assert_eq!(spread.median().unwrap(), Some(2));
+        assert_eq!(median_alignment(&spread).unwrap().map(|a| a.fuel), Some(16));
+
+        // Duplicate positions straddling the middle
+        let stacked = crabs("1,1,1,5,5,5");
+        assert_eq!(stacked.median().unwrap(), Some(1));
+        assert_eq!(
+            median_alignment(&stacked).unwrap().map(|a| a.fuel),
+            Some(12)
+        );
+    }
+
+    /// Check every fast search against the brute force search
+    fn check_against_brute_force(crabs: &CrabList) {
+        assert_eq!(
+            median_alignment(crabs).unwrap().map(|a| a.fuel),
+            brute_force(crabs, linear_cost)
+        );
+        assert_eq!(
+            best_alignment(crabs, linear_cost).unwrap().map(|a| a.fuel),
+            brute_force(crabs, linear_cost)
+        );
+        assert_eq!(
+            best_alignment(crabs, triangular_cost)
+                .unwrap()
+                .map(|a| a.fuel),
+            brute_force(crabs, triangular_cost)
+        );
+    }
+
+    #[test]
+    fn test_single_position() {
+        let crabs = crabs("3,3,3,3");
+        check_against_brute_force(&crabs);
+
+        assert_eq!(
+            best_alignment(&crabs, triangular_cost).unwrap(),
+            Some(Alignment {
+                position: 3,
+                fuel: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_no_crabs() {
+        for input in ["", "\n", "  "] {
+            assert_eq!(
+                part1(input).unwrap_err().to_string(),
+                "There are no crabs 🙁"
+            );
+            assert_eq!(
+                part2(input).unwrap_err().to_string(),
+                "There are no crabs 🙁"
+            );
+        }
+    }
+
+    #[test]
+    fn test_one_crab() {
+        let alone = Alignment {
+            position: 12,
+            fuel: 0,
         };
 
-        let mut cahoot = crabs
-            .population
-            .remove(&origin)
-            .expect("inconsitent iterator result");
+        assert_eq!(solve_part1("12\n").unwrap(), alone);
+        assert_eq!(solve_part2("12\n").unwrap(), alone);
+    }
+
+    #[test]
+    fn test_weighted_input() {
+        let crabs = crabs("5:1000000,0");
+        assert_eq!(crabs.crab_counts[&5], 1_000_000);
+
+        assert_eq!(
+            median_alignment(&crabs).unwrap(),
+            Some(Alignment {
+                position: 5,
+                fuel: 5
+            })
+        );
+        assert_eq!(
+            best_alignment(&crabs, triangular_cost).unwrap(),
+            Some(Alignment {
+                position: 5,
+                fuel: 15
+            })
+        );
+
+        // Weighted and repeated positions can be mixed
+        assert_eq!(part1("16,1:2,2:3,0,4,7,14").unwrap(), 37);
+        assert_eq!(part2("16,1:2,2:3,0,4,7,14").unwrap(), 168);
+    }
+
+    #[test]
+    fn test_malformed_weight() {
+        for (input, message) in [
+            (
+                "1,2:x,3",
+                "failed to parse token \"2:x\" at index 1: invalid count: \
+                invalid digit found in string",
+            ),
+            (
+                "1,2,:4",
+                "failed to parse token \":4\" at index 2: invalid position: \
+                cannot parse integer from empty string",
+            ),
+            (
+                "1,2,3:0",
+                "failed to parse token \"3:0\" at index 2: count must be at least 1",
+            ),
+            (
+                "1:-5,10,3",
+                "failed to parse token \"1:-5\" at index 0: count must be at least 1",
+            ),
+            (
+                "0:-1000,100",
+                "failed to parse token \"0:-1000\" at index 0: count must be at least 1",
+            ),
+        ] {
+            let err = part1(input).unwrap_err();
+            assert_eq!(
+                format!("{:#}", err),
+                format!("failed to parse input: {}", message)
+            );
+        }
+    }
+
+    #[test]
+    fn test_huge_counts() {
+        // Most positions take far more fuel than fits in an i64, but the best
+        // one doesn't
+        let input = "0:5000000000000,100000";
+        assert_eq!(part1(input).unwrap(), 100_000);
+        assert_eq!(part2(input).unwrap(), 5_000_050_000);
+
+        let input = "0:9223372036854775807,5";
+        assert_eq!(
+            part1(input).unwrap_err().to_string(),
+            "there are too many crabs to find the middle one"
+        );
+        assert_eq!(part2(input).unwrap(), 15);
+
+        assert_eq!(
+            part1("3:9223372036854775807,3").unwrap_err().to_string(),
+            "too many crabs at position 3"
+        );
+        assert_eq!(
+            part2("0:4611686018427387904,2:4611686018427387904")
+                .unwrap_err()
+                .to_string(),
+            "aligning at 1 takes too much fuel"
+        );
+    }
+
+    #[test]
+    fn test_extreme_positions() {
+        let input = "-2147483648,2147483647";
+        assert_eq!(part1(input).unwrap(), 4_294_967_295);
+        assert_eq!(part2(input).unwrap(), 4_611_686_018_427_387_904);
+    }
+
+    #[test]
+    fn test_two_crabs() {
+        for input in ["0,1", "0,2", "-5,10", "7,3"] {
+            check_against_brute_force(&crabs(input));
+        }
 
-        fuel += cahoot.step();
+        // Triangular cost pulls towards the middle, even though every
+        // position between the crabs costs the same linear fuel
+        assert_eq!(
+            best_alignment(&crabs("0,10"), triangular_cost)
+                .unwrap()
+                .map(|a| a.position),
+            Some(5)
+        );
+    }
+
+    #[test]
+    fn test_wide_ternary_search() {
+        // Far wider than SCAN_WIDTH, so the ternary search does most of the work
+        let crabs = crabs("-100000,3,7,7,20000,250000");
+        check_against_brute_force(&crabs);
+    }
 
-        match crabs.population.entry(destination) {
-            Entry::Vacant(slot) => {
-                slot.insert(cahoot);
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(3))]
+
+        #[test]
+        fn test_parallel_scan_matches_sequential(
+            positions in prop::collection::vec(0i32..5000, 1000),
+        ) {
+            let crabs: CrabList = positions.into_iter().collect();
+            let (&min, _) = crabs.crab_counts.first_key_value().unwrap();
+            let (&max, _) = crabs.crab_counts.last_key_value().unwrap();
+
+            for cost in [linear_cost, triangular_cost] {
+                prop_assert_eq!(
+                    scan_alignment(&crabs, min..=max, cost).unwrap().map(|a| a.fuel),
+                    brute_force(&crabs, cost)
+                );
             }
-            Entry::Occupied(mut existing_cahoot) => existing_cahoot.get_mut().merge(cahoot),
+        }
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(128))]
+
+        #[test]
+        fn test_fast_matches_brute_force(
+            positions in prop::collection::vec(-500i32..500, 1..200),
+        ) {
+            check_against_brute_force(&positions.into_iter().collect());
+        }
+
+        #[test]
+        fn test_clustered_matches_brute_force(
+            positions in prop::collection::vec(0i32..10, 1..50),
+            outliers in prop::collection::vec(-1000i32..1000, 0..3),
+        ) {
+            check_against_brute_force(&positions.into_iter().chain(outliers).collect());
         }
     }
 }
@@ -1,8 +1,15 @@
-use anyhow::Context;
+use std::{
+    fmt::{self, Write as _},
+    sync::LazyLock,
+};
+
+use anyhow::{anyhow, Context};
 use enum_map::{enum_map, Enum, EnumMap};
+use itertools::Itertools;
 use nom::{
     branch::alt,
-    character::complete::{char, multispace0, multispace1},
+    bytes::complete::tag,
+    character::complete::{char, line_ending, multispace0, multispace1, space0, space1},
     combinator::{eof, success},
     IResult, Parser,
 };
@@ -12,9 +19,11 @@ use nom_supreme::{
     multi::{collect_separated_terminated, parse_separated_terminated},
     ParserExt,
 };
+use rayon::prelude::*;
+use thiserror::Error;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Enum)]
-enum Segment {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Enum)]
+pub enum Segment {
     A,
     B,
     C,
@@ -26,23 +35,76 @@ enum Segment {
 
 use Segment::*;
 
-use crate::library::IterExt;
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char(match self {
+            A => 'a',
+            B => 'b',
+            C => 'c',
+            D => 'd',
+            E => 'e',
+            F => 'f',
+            G => 'g',
+        })
+    }
+}
+
+use crate::{library::format_parse_error, Part, Solver};
 
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 struct SegmentSet {
     segments: EnumMap<Segment, bool>,
 }
 
+/// Shows the lit segments, like `acf`
+impl fmt::Display for SegmentSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.segments
+            .iter()
+            .filter(|(_, &on)| on)
+            .try_for_each(|(segment, _)| write!(f, "{}", segment))
+    }
+}
+
+/// The segments that are lit for each digit
+const DIGIT_SEGMENTS: [&[Segment]; 10] = [
+    &[A, B, C, E, F, G],    // 0
+    &[C, F],                // 1
+    &[A, C, D, E, G],       // 2
+    &[A, C, D, F, G],       // 3
+    &[B, C, D, F],          // 4
+    &[A, B, D, F, G],       // 5
+    &[A, B, D, E, F, G],    // 6
+    &[A, C, F],             // 7
+    &[A, B, C, D, E, F, G], // 8
+    &[A, B, C, D, F, G],    // 9
+];
+
+static DIGIT_SHAPES: LazyLock<[SegmentSet; 10]> =
+    LazyLock::new(|| DIGIT_SEGMENTS.map(SegmentSet::from_segments));
+
 impl SegmentSet {
+    fn from_segments(segments: &[Segment]) -> Self {
+        let mut set = SegmentSet::default();
+        segments
+            .iter()
+            .for_each(|&segment| set.segments[segment] = true);
+        set
+    }
+
+    /// The correctly wired shape of a digit. Panics if `digit` isn't a single
+    /// decimal digit.
+    fn from_digit(digit: usize) -> Self {
+        DIGIT_SHAPES[digit]
+    }
+
     fn count(&self) -> usize {
         self.segments.values().filter(|&b| *b).count()
     }
 
     // What digit is this, if any?
     fn identify(&self) -> Option<usize> {
-        get_digit_shapes()
-            .iter()
-            .position(|candidate| self == candidate)
+        DIGIT_SHAPES.iter().position(|candidate| self == candidate)
     }
 }
 
@@ -63,7 +125,7 @@ fn parse_segment_set(input: &str) -> IResult<&str, SegmentSet, ErrorTree<&str>>
     parse_separated_terminated(
         parse_segment.context("segment"),
         success(()),
-        multispace1.or(eof).peek(),
+        multispace1.or(tag("|")).or(eof).peek(),
         SegmentSet::default,
         |mut set, segment| {
             set.segments[segment] = true;
@@ -73,24 +135,79 @@ fn parse_segment_set(input: &str) -> IResult<&str, SegmentSet, ErrorTree<&str>>
     .parse(input)
 }
 
+#[derive(Debug, Clone, Error)]
+enum SignalError {
+    #[error("the signal {0} appears more than once")]
+    Duplicate(SegmentSet),
+
+    #[error(
+        "expected {expected} signals with {length} segments, but found {}",
+        describe_signals(found)
+    )]
+    WrongLength {
+        length: usize,
+        expected: usize,
+        found: Vec<SegmentSet>,
+    },
+}
+
+/// Describe a list of signals, like `2 (abc abd)`
+fn describe_signals(signals: &[SegmentSet]) -> String {
+    match signals {
+        [] => "0".to_owned(),
+        signals => format!("{} ({})", signals.len(), signals.iter().join(" ")),
+    }
+}
+
+/// Check that the signals are all different, and that there are as many
+/// signals of each length as there are digits with that many segments
+fn validate_signals(signals: [SegmentSet; 10]) -> Result<[SegmentSet; 10], SignalError> {
+    if let Some(&duplicate) = signals.iter().duplicates().next() {
+        return Err(SignalError::Duplicate(duplicate));
+    }
+
+    for length in 0..=7 {
+        let expected = DIGIT_SHAPES
+            .iter()
+            .filter(|shape| shape.count() == length)
+            .count();
+        let found = signals
+            .iter()
+            .filter(|signal| signal.count() == length)
+            .copied()
+            .collect_vec();
+
+        if found.len() != expected {
+            return Err(SignalError::WrongLength {
+                length,
+                expected,
+                found,
+            });
+        }
+    }
+
+    Ok(signals)
+}
+
 fn parse_signals(input: &str) -> IResult<&str, [SegmentSet; 10], ErrorTree<&str>> {
     parse_segment_set
         .context("signal")
-        .separated_array(multispace1)
+        .separated_array(space1)
+        .map_res(validate_signals)
         .parse(input)
 }
 
 fn parse_output_digits(input: &str) -> IResult<&str, [SegmentSet; 4], ErrorTree<&str>> {
     parse_segment_set
         .context("output digit")
-        .separated_array(multispace1)
+        .separated_array(space1)
         .parse(input)
 }
 
 fn parse_display(input: &str) -> IResult<&str, Display, ErrorTree<&str>> {
     parse_signals
         .context("signal data")
-        .terminated(char('|').delimited_by(multispace0))
+        .terminated(char('|').delimited_by(space0))
         .and(parse_output_digits.context("output digits"))
         .map(|(signals, output_digits)| Display {
             signals,
@@ -99,12 +216,17 @@ fn parse_display(input: &str) -> IResult<&str, Display, ErrorTree<&str>> {
         .parse(input)
 }
 
+/// Each display is on its own line. Signals and output digits are separated
+/// by horizontal whitespace only, so that a display with too few or too many
+/// of either is an error, rather than spilling over onto the next line.
 fn parse_all_displays(input: &str) -> Result<Vec<Display>, ErrorTree<Location>> {
     let parser = collect_separated_terminated(
         parse_display.context("display"),
-        multispace1,
+        line_ending.preceded_by(space0).terminated(multispace0),
         eof.preceded_by(multispace0),
-    );
+    )
+    .preceded_by(multispace0);
+
     final_parser(parser)(input)
 }
 
@@ -114,15 +236,56 @@ struct Display {
     output_digits: [SegmentSet; 4],
 }
 
-#[derive(Debug, Clone, Copy)]
-struct DisplayWiring {
+/// The reason that the wiring of a display couldn't be worked out from its
+/// signals
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum WiringError {
+    #[error(
+        "could not find the '{digit}' digit to disambiguate {} vs {}",
+        ambiguous.0,
+        ambiguous.1
+    )]
+    MissingDigit {
+        digit: usize,
+        ambiguous: (Segment, Segment),
+    },
+
+    #[error(
+        "input segment {segment} is lit in {count} signals, which doesn't match any output segment"
+    )]
+    UnexpectedCount { segment: Segment, count: u8 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayWiring {
     // Key: the correct output signal
     // value: the input  segment
     wires: EnumMap<Segment, Segment>,
 }
 
+/// Shows which output segment each input segment is wired to, like
+/// `a->c, b->f, ...`, ordered by input segment
+impl fmt::Display for DisplayWiring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let wires = self
+            .wires()
+            .iter()
+            .map(|(output, &input)| (input, output))
+            .sorted()
+            .map(|(input, output)| format!("{}->{}", input, output));
+
+        write!(f, "{}", wires.format(", "))
+    }
+}
+
 impl DisplayWiring {
-    fn compute(signals: &[SegmentSet; 10]) -> Option<Self> {
+    /// For each correct output segment, the scrambled input segment that
+    /// drives it
+    pub fn wires(&self) -> &EnumMap<Segment, Segment> {
+        &self.wires
+    }
+
+    fn compute(signals: &[SegmentSet; 10]) -> Result<Self, WiringError> {
         let mut counts: EnumMap<Segment, u8> = EnumMap::default();
 
         signals.iter().for_each(|signal| {
@@ -133,40 +296,46 @@ impl DisplayWiring {
                 .for_each(|(segment, _)| counts[segment] += 1);
         });
 
-        counts
-            .iter()
-            .map(|(input_signal, &count)| {
-                Some((
-                    match count {
-                        6 => B,
-                        4 => E,
-                        9 => F,
-                        // Either A or C; distinguish by identifying the 1
-                        8 => match signals.iter().find(|signal| signal.count() == 2)?.segments
-                            [input_signal]
-                        {
-                            true => C,
-                            false => A,
-                        },
-                        // either D or G, distinguish by identifying the 4
-                        7 => match signals.iter().find(|signal| signal.count() == 4)?.segments
-                            [input_signal]
-                        {
-                            true => D,
-                            false => G,
-                        },
-                        _ => return None,
-                    },
-                    input_signal,
-                ))
-            })
-            .map(|opt| opt.ok_or(()))
-            .use_oks(|signals| {
-                let mut wires = enum_map! { _ => A};
-                wires.extend(signals);
-                DisplayWiring { wires }
-            })
-            .ok()
+        // Find the signal for a digit with a unique number of segments, which
+        // is used to tell apart a pair of segments with the same count
+        let find_digit = |digit: usize, ambiguous| {
+            let length = SegmentSet::from_digit(digit).count();
+
+            signals
+                .iter()
+                .find(|signal| signal.count() == length)
+                .ok_or(WiringError::MissingDigit { digit, ambiguous })
+        };
+
+        let mut wires = enum_map! { _ => A };
+
+        for (input_signal, &count) in counts.iter() {
+            let output_signal = match count {
+                6 => B,
+                4 => E,
+                9 => F,
+                // Either A or C; distinguish by identifying the 1
+                8 => match find_digit(1, (A, C))?.segments[input_signal] {
+                    true => C,
+                    false => A,
+                },
+                // either D or G, distinguish by identifying the 4
+                7 => match find_digit(4, (D, G))?.segments[input_signal] {
+                    true => D,
+                    false => G,
+                },
+                count => {
+                    return Err(WiringError::UnexpectedCount {
+                        segment: input_signal,
+                        count,
+                    })
+                }
+            };
+
+            wires[output_signal] = input_signal;
+        }
+
+        Ok(DisplayWiring { wires })
     }
 
     fn get_digit(self, input: SegmentSet) -> SegmentSet {
@@ -176,129 +345,328 @@ impl DisplayWiring {
     }
 }
 
-fn get_digit_shapes() -> [SegmentSet; 10] {
-    [
-        // 0
-        SegmentSet {
-            segments: enum_map! {
-                A | B | C | E | F | G => true,
-                _ => false,
-            },
-        },
-        // 1
-        SegmentSet {
-            segments: enum_map! {
-                C | F => true,
-                _ => false,
-            },
-        },
-        // 2
-        SegmentSet {
-            segments: enum_map! {
-             A | C | D | E | G => true,
-                _ => false,
-            },
-        },
-        // 3
-        SegmentSet {
-            segments: enum_map! {
-             A | C | D | F | G  => true,
-                _ => false,
-            },
-        },
-        // 4
-        SegmentSet {
-            segments: enum_map! {
-             B | C | D | F => true,
-                _ => false,
-            },
-        },
-        // 5
-        SegmentSet {
-            segments: enum_map! {
-            A | B | D | F | G => true,
-               _ => false,
-               },
-        },
-        // 6
-        SegmentSet {
-            segments: enum_map! {
-             A | B | D | E| F | G => true,
-                _ => false,
-            },
-        },
-        // 7
-        SegmentSet {
-            segments: enum_map! {
-             A | C | F => true,
-                _ => false,
-            },
-        },
-        // 8
-        SegmentSet {
-            segments: enum_map! {
-                _ => true
-            },
-        },
-        // 9
-        SegmentSet {
-            segments: enum_map! {
-             A | B | C | D | F | G => true,
-                _ => false,
-            },
-        },
-    ]
-}
+impl Display {
+    /// Perform the solve- figure out which input segments are associated with
+    /// which output segments
+    fn wiring(&self) -> anyhow::Result<DisplayWiring> {
+        DisplayWiring::compute(&self.signals).context("failed to compute display wiring")
+    }
 
-pub fn part1(input: &str) -> anyhow::Result<i32> {
-    let display_data = parse_all_displays(input).context("parse error")?;
-    let digits = get_digit_shapes();
+    /// Identify each of the four output digits, given the wiring
+    fn output_values(&self, display_wiring: DisplayWiring) -> anyhow::Result<[usize; 4]> {
+        let mut values = [0; 4];
 
-    let mut digit_counts = [0; 10];
+        for (value, &digit) in values.iter_mut().zip(&self.output_digits) {
+            // Run the input signal through the wiring to get the corrected
+            // segments, then figure out which digit is on the display
+            *value = display_wiring
+                .get_digit(digit)
+                .identify()
+                .context("no matching digit")?;
+        }
 
-    for display in display_data {
-        let wiring =
-            DisplayWiring::compute(&display.signals).context("failed to compute display wiring")?;
+        Ok(values)
+    }
 
-        for output_digit in display.output_digits {
-            let digit = wiring.get_digit(output_digit);
-            let digit = digits
+    /// Decode the four digit number shown on this display, given its wiring
+    fn decode(&self, display_wiring: DisplayWiring) -> anyhow::Result<u32> {
+        self.output_values(display_wiring).map(|values| {
+            values
                 .iter()
-                .position(|&candidate| digit == candidate)
-                .context("no matching digit")?;
+                .fold(0, |number, &digit| number * 10 + digit as u32)
+        })
+    }
+}
 
-            digit_counts[digit] += 1;
-        }
+/// Parse all the displays and solve each of them in parallel. If any of them
+/// fail, the error from the earliest one is returned.
+fn solve_displays<T: Send>(
+    input: &str,
+    solve: impl Fn(&Display) -> anyhow::Result<T> + Sync,
+) -> anyhow::Result<Vec<T>> {
+    let display_data = parse_all_displays(input)
+        .map_err(|err| anyhow!(format_parse_error(input, &err)))
+        .context("parse error")?;
+
+    let solutions: Vec<anyhow::Result<T>> = display_data
+        .par_iter()
+        .enumerate()
+        .map(|(index, display)| {
+            solve(display).with_context(|| format!("failed to decode display {}", index + 1))
+        })
+        .collect();
+
+    solutions.into_iter().collect()
+}
+
+pub fn part1(input: &str) -> anyhow::Result<usize> {
+    let easy_digit_counts = solve_displays(input, |display| {
+        let values = display.output_values(display.wiring()?)?;
+
+        Ok(values
+            .iter()
+            .filter(|&&digit| matches!(digit, 1 | 4 | 7 | 8))
+            .count())
+    })?;
+
+    Ok(easy_digit_counts.iter().sum())
+}
+
+/// Compute the wiring of each display, and use it to decode the four digit
+/// number shown on that display
+pub fn decode_with_wirings(input: &str) -> anyhow::Result<Vec<(DisplayWiring, u32)>> {
+    solve_displays(input, |display| {
+        let wiring = display.wiring()?;
+        Ok((wiring, display.decode(wiring)?))
+    })
+}
+
+/// Decode the four digit number shown on each display, in order
+pub fn decode_displays(input: &str) -> anyhow::Result<Vec<u32>> {
+    decode_with_wirings(input).map(|decoded| decoded.into_iter().map(|(_, value)| value).collect())
+}
+
+pub fn part2(input: &str) -> anyhow::Result<u32> {
+    decode_displays(input).map(|values| values.iter().sum())
+}
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
     }
 
-    Ok([1, 4, 7, 8].iter().map(|&digit| digit_counts[digit]).sum())
+    fn details(&self, part: Part, input: &str) -> anyhow::Result<Option<String>> {
+        match part {
+            Part::Part1 => Ok(None),
+            Part::Part2 => decode_with_wirings(input).map(|decoded| {
+                Some(
+                    decoded
+                        .iter()
+                        .map(|(wiring, value)| format!("{:04} ({})", value, wiring))
+                        .join("\n"),
+                )
+            }),
+        }
+    }
 }
 
-pub fn part2(input: &str) -> anyhow::Result<usize> {
-    let display_data = parse_all_displays(input).context("parse error")?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    // I'd much rather have this be an iterator sum, but the control flow
-    // (dealing with Results) got pretty hairy
-    let mut total = 0;
+    const SAMPLE: &str = "\
+be cfbegad cbdgef fgaecd cgeb fdcge agebfd fecdb fabcd edb | fdgacbe cefdb cefbgd gcbe
+edbfga begcd cbg gc gcadebf fbgde acbgfd abcde gfcbed gfec | fcgedb cgb dgebacf gc
+fgaebd cg bdaec gdafb agbcfd gdcbef bgcad gfac gcb cdgabef | cg cg fdcagb cbg
+fbegcd cbd adcefb dageb afcb bc aefdc ecdab fgdeca fcdbega | efabcd cedba gadfec cb
+aecbfdg fbg gf bafeg dbefa fcge gcbea fcaegb dgceab fcbdga | gecf egdcabf bgf bfgea
+fgeab ca afcebg bdacfeg cfaedg gcfdb baec bfadeg bafgc acf | gebdcfa ecba ca fadegcb
+dbcfg fgd bdegcaf fgec aegbdf ecdfab fbedc dacgb gdcebf gf | cefg dcbef fcge gbcadfe
+bdfegc cbegaf gecbf dfcage bdacg ed bedf ced adcbefg gebcd | ed bcgafe cdgba cbgef
+egadfb cdbfeg cegd fecab cgb gbdefca cg fgcdab egfdb bfceg | gbdfcae bgc cg cgb
+gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce
+";
 
-    for display_data in display_data {
-        // Perform the solve- figure out which input segments are associated
-        // with which output segments
-        let display_wiring = DisplayWiring::compute(&display_data.signals)
-            .context("failed to compute display wiring")?;
+    #[test]
+    fn test_sample() {
+        assert_eq!(part1(SAMPLE).unwrap(), 26);
+        assert_eq!(part2(SAMPLE).unwrap(), 61229);
+    }
 
-        // iterate over the 4 output digits. Associate each one with an
-        // exponent so that the total sum can be accumulated
-        for (&digit, exp) in display_data.output_digits.iter().rev().zip(0..) {
-            // Run the input signal through the wiring to get the corrected
-            // segments
-            let digit = display_wiring.get_digit(digit);
+    #[test]
+    fn test_decoded_values() {
+        let values = decode_displays(SAMPLE).unwrap();
+
+        assert_eq!(values.len(), 10);
+        assert_eq!(values[..4], [8394, 9781, 1197, 9361]);
+        assert_eq!(values.last(), Some(&4315));
+    }
+
+    #[test]
+    fn test_digit_shapes() {
+        for digit in 0..=9 {
+            assert_eq!(SegmentSet::from_digit(digit).identify(), Some(digit));
+        }
+
+        assert_eq!(SegmentSet::from_digit(7).to_string(), "acf");
+        assert_eq!(SegmentSet::from_digit(8).count(), 7);
+        assert_eq!(SegmentSet::default().identify(), None);
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        let sequential: u32 = parse_all_displays(SAMPLE)
+            .unwrap()
+            .iter()
+            .map(|display| display.decode(display.wiring().unwrap()).unwrap())
+            .sum();
+
+        assert_eq!(part2(SAMPLE).unwrap(), sequential);
+    }
 
-            // Figure out which digit is on the display
-            let digit = digit.identify().context("no matching digit")?;
-            total += digit * 10usize.pow(exp);
+    #[test]
+    fn test_earliest_error() {
+        // Displays 3 and 7 both have a segment that isn't lit the right
+        // number of times
+        let input = SAMPLE
+            .lines()
+            .enumerate()
+            .map(|(index, line)| match index {
+                2 | 6 => "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ag | ab ab ab ab",
+                _ => line,
+            })
+            .join("\n");
+
+        for _ in 0..10 {
+            let err = part2(&input).unwrap_err();
+            assert_eq!(err.to_string(), "failed to decode display 3");
         }
     }
 
-    Ok(total)
+    #[test]
+    fn test_whitespace() {
+        let expected = decode_displays(SAMPLE).unwrap();
+
+        let crlf = SAMPLE.replace('\n', "\r\n");
+        assert_eq!(decode_displays(&crlf).unwrap(), expected);
+
+        let tabs = SAMPLE.replace(' ', "\t");
+        assert_eq!(decode_displays(&tabs).unwrap(), expected);
+
+        let ragged = SAMPLE
+            .lines()
+            .map(|line| format!("\t  {} \t\r\n\r\n", line.replace(" | ", "|")))
+            .collect::<String>();
+        assert_eq!(decode_displays(&ragged).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_signal_and_digit_counts() {
+        let display =
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf";
+
+        // Missing a signal
+        let short = display.replace("dab ", "");
+        assert!(decode_displays(&short).is_err());
+
+        // Extra output digit
+        let long = format!("{} ab", display);
+        assert!(decode_displays(&long).is_err());
+
+        // A display broken across lines
+        let broken = display.replace(" | ", "\n| ");
+        assert!(decode_displays(&broken).is_err());
+    }
+
+    #[test]
+    fn test_wiring() {
+        let input =
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf";
+        let (wiring, value) = decode_with_wirings(input).unwrap()[0];
+
+        assert_eq!(value, 5353);
+        assert_eq!(wiring.wires()[A], D);
+        assert_eq!(wiring.wires()[G], C);
+        assert_eq!(
+            wiring.to_string(),
+            "a->c, b->f, c->g, d->a, e->b, f->d, g->e"
+        );
+    }
+
+    /// Parse signals without validating them
+    fn signals_of(input: &str) -> [SegmentSet; 10] {
+        input
+            .split_whitespace()
+            .map(|signal| parse_segment_set(signal).unwrap().1)
+            .collect_vec()
+            .try_into()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_missing_unique_digit() {
+        // There's no signal with 2 segments, so there's no 1
+        let signals = signals_of("acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb abc");
+        let err = DisplayWiring::compute(&signals).unwrap_err();
+
+        assert_eq!(
+            err,
+            WiringError::MissingDigit {
+                digit: 1,
+                ambiguous: (A, C)
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "could not find the '1' digit to disambiguate a vs c"
+        );
+    }
+
+    #[test]
+    fn test_unexpected_segment_count() {
+        let signals = signals_of("acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ag");
+
+        assert_eq!(
+            DisplayWiring::compute(&signals).unwrap_err(),
+            WiringError::UnexpectedCount {
+                segment: G,
+                count: 5
+            }
+        );
+
+        let err = part2(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ag | cdfeb fcadb cdfeb cdbaf",
+        )
+        .unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "failed to decode display 1: failed to compute display wiring: \
+            input segment g is lit in 5 signals, which doesn't match any output segment"
+        );
+    }
+
+    #[test]
+    fn test_duplicate_signal() {
+        let err = part2(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cdfbe ab | cdfeb fcadb cdfeb cdbaf",
+        )
+        .unwrap_err();
+
+        assert!(format!("{:#}", err).contains("the signal bcdef appears more than once"));
+    }
+
+    #[test]
+    fn test_wrong_signal_lengths() {
+        let err = part2(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb abc | cdfeb fcadb cdfeb cdbaf",
+        )
+        .unwrap_err();
+
+        assert!(format!("{:#}", err).contains("expected 1 signals with 2 segments, but found 0"));
+
+        let signals = signals_of("acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb abc");
+        assert_eq!(
+            validate_signals(signals).unwrap_err().to_string(),
+            "expected 1 signals with 2 segments, but found 0"
+        );
+
+        let signals = signals_of("acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb abc ab");
+        assert_eq!(
+            validate_signals(signals).unwrap_err().to_string(),
+            "expected 1 signals with 3 segments, but found 2 (abd abc)"
+        );
+    }
+
+    #[test]
+    fn test_leading_zeroes() {
+        let input =
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cagedb ab cagedb dab";
+        assert_eq!(decode_displays(input).unwrap(), [107]);
+    }
 }
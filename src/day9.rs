@@ -1,6 +1,6 @@
 use std::{
-    collections::{BinaryHeap, HashMap},
-    iter,
+    cmp::Reverse,
+    collections::{HashMap, HashSet},
 };
 
 use anyhow::Context;
@@ -8,38 +8,46 @@ use gridly::prelude::*;
 use gridly_grids::VecGrid;
 use itertools::Itertools;
 
-use crate::library::Counter;
-
-fn parse_grid(input: &str) -> anyhow::Result<VecGrid<i32>> {
-    let rows: Vec<Vec<i32>> = input
-        .lines()
-        .map(|line| {
-            line.chars()
-                .map(|c| c.to_digit(10).context("parsing digit"))
-                .map_ok(|digit| digit.try_into().unwrap())
-                .try_collect()
-        })
-        .try_collect()?;
+use crate::{
+    grid::{neighbors, parse_digit_grid, render_grid},
+    library::Counter,
+    Part, Solver,
+};
 
-    VecGrid::new_from_rows(rows).context("inconsistent row length")
+/// The neighbors of each location: the 4 orthogonal neighbors, or all 8
+/// touching neighbors if `diagonals` is set
+fn adjacencies(diagonals: bool) -> &'static [Vector] {
+    match diagonals {
+        false => &ORTHOGONAL_ADJACENCIES,
+        true => &TOUCHING_ADJACENCIES,
+    }
 }
 
-pub fn part1(input: &str) -> anyhow::Result<i32> {
-    let grid = parse_grid(input)?;
+/// Find every location that's lower than all of its neighbors, along with its
+/// height, in reading order
+pub fn low_points(input: &str, diagonals: bool) -> anyhow::Result<Vec<(Location, i32)>> {
+    let grid: VecGrid<i32> = parse_digit_grid(input).context("failed to parse height map")?;
+    let adjacencies = adjacencies(diagonals);
 
     Ok(grid
         .rows()
         .iter()
         .flat_map(|row| row.iter_with_locations())
         .filter(|&(loc, &cell)| {
-            EACH_DIRECTION
-                .iter()
-                .map(|&dir| loc + dir)
-                .filter_map(|neighbor_loc| grid.get(neighbor_loc).ok())
-                .all(|&neighbor| neighbor > cell)
+            neighbors(&grid, loc, adjacencies).all(|(_, &neighbor)| neighbor > cell)
         })
-        .map(|(_, &min)| min + 1)
-        .sum())
+        .map(|(loc, &height)| (loc, height))
+        .collect())
+}
+
+/// The sum of the risk levels of all the low points
+pub fn total_risk(input: &str, diagonals: bool) -> anyhow::Result<i32> {
+    low_points(input, diagonals)
+        .map(|low_points| low_points.iter().map(|&(_, height)| height + 1).sum())
+}
+
+pub fn part1(input: &str) -> anyhow::Result<i32> {
+    total_risk(input, false)
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -47,38 +55,92 @@ struct BasinId {
     low_point: Location,
 }
 
-// returning the basin that input
+/// Find the plateau containing `location`: every location connected to it
+/// through neighbors of the same height, including `location` itself.
+fn find_plateau(location: Location, grid: &VecGrid<i32>, adjacencies: &[Vector]) -> Vec<Location> {
+    let height = grid[location];
+    let mut plateau = vec![location];
+    let mut seen = HashSet::from([location]);
+    let mut next = 0;
+
+    while let Some(&current) = plateau.get(next) {
+        next += 1;
+
+        for (neighbor, &neighbor_height) in neighbors(grid, current, adjacencies) {
+            if neighbor_height == height && seen.insert(neighbor) {
+                plateau.push(neighbor);
+            }
+        }
+    }
+
+    plateau
+}
+
+/// Identify the basin that `input` flows into. This walks downhill from
+/// `input`, keeping the path on an explicit stack, until it reaches either a
+/// low point or a location whose basin is already known; then every location
+/// on the path is assigned to that basin.
+///
+/// A plateau of neighboring locations with equal heights is always part of a
+/// single basin. If any location on the plateau has a lower neighbor, the
+/// whole plateau drains into the lowest of them; otherwise the plateau is the
+/// bottom of its basin, and the basin is identified by the plateau's first
+/// location in reading order.
 fn identify_basin(
-    height: i32,
     input: Location,
     grid: &VecGrid<i32>,
+    adjacencies: &[Vector],
     basins: &mut HashMap<Location, BasinId>,
 ) -> BasinId {
-    // Find the value and location of the neighbor with the lowest height,
-    // or None if this is the lowest
-    let min_neighbor = EACH_DIRECTION
-        .iter()
-        .map(|&dir| input + dir)
-        .filter_map(|neighbor_loc| grid.get(neighbor_loc).map(|cell| (cell, neighbor_loc)).ok())
-        .filter(|&(&ncell, _)| ncell < height)
-        .min_by_key(|&(&ncell, _)| ncell);
-
-    let basin_id = match min_neighbor {
-        // Found a lower neighbor; identify the basin associated with it
-        Some((&neighbor_height, neighbor_location)) => match basins.get(&neighbor_location) {
-            Some(&basin_id) => basin_id,
-            None => identify_basin(neighbor_height, neighbor_location, grid, basins),
-        },
-        // There are no lower neighbors; this location is the basin.
-        None => BasinId { low_point: input },
+    let mut path = Vec::new();
+    let mut location = input;
+
+    let basin_id = loop {
+        if let Some(&basin_id) = basins.get(&location) {
+            break basin_id;
+        }
+
+        let height = grid[location];
+        let plateau = find_plateau(location, grid, adjacencies);
+
+        // Find the location of the plateau's neighbor with the lowest height,
+        // or None if this plateau is the lowest
+        let min_neighbor = plateau
+            .iter()
+            .flat_map(|&location| neighbors(grid, location, adjacencies))
+            .filter(|&(_, &ncell)| ncell < height)
+            .min_by_key(|&(_, &ncell)| ncell)
+            .map(|(neighbor_location, _)| neighbor_location);
+
+        path.extend(&plateau);
+
+        match min_neighbor {
+            // Found a lower neighbor; continue down into its basin
+            Some(neighbor_location) => location = neighbor_location,
+
+            // There are no lower neighbors; this plateau is the basin.
+            None => {
+                break BasinId {
+                    low_point: plateau
+                        .into_iter()
+                        .min_by_key(|location| (location.row, location.column))
+                        .expect("a plateau always contains at least one location"),
+                }
+            }
+        }
     };
 
-    basins.insert(input, basin_id);
+    path.into_iter().for_each(|location| {
+        basins.insert(location, basin_id);
+    });
+
     basin_id
 }
 
-pub fn part2(input: &str) -> anyhow::Result<usize> {
-    let grid = parse_grid(input)?;
+/// Assign every location lower than 9 to a basin
+fn assign_basins(grid: &VecGrid<i32>, diagonals: bool) -> HashMap<Location, BasinId> {
+    let adjacencies = adjacencies(diagonals);
+
     // key - location :: value - basin_id
     let mut basins: HashMap<Location, BasinId> = HashMap::new();
 
@@ -86,14 +148,202 @@ pub fn part2(input: &str) -> anyhow::Result<usize> {
         .iter()
         .flat_map(|row| row.iter_with_locations())
         .filter(|&(_, &cell)| cell < 9)
-        .for_each(|(loc, &cell)| {
-            identify_basin(cell, loc, &grid, &mut basins);
+        .for_each(|(loc, _)| {
+            identify_basin(loc, grid, adjacencies, &mut basins);
         });
 
+    basins
+}
+
+/// The size of every basin, largest first
+pub fn basin_sizes(input: &str, diagonals: bool) -> anyhow::Result<Vec<usize>> {
+    let grid: VecGrid<i32> = parse_digit_grid(input).context("failed to parse height map")?;
+    let basins = assign_basins(&grid, diagonals);
+
     let basin_counts: Counter<BasinId> = basins.values().copied().collect();
 
-    let mut sorted_counts: BinaryHeap<usize> =
-        basin_counts.iter_counts().map(|(_, count)| count).collect();
+    let mut sizes: Vec<usize> = basin_counts.iter_counts().map(|(_, count)| count).collect();
+    sizes.sort_unstable_by_key(|&size| Reverse(size));
+
+    Ok(sizes)
+}
+
+/// The characters used to label basins on the rendered map. If there are more
+/// basins than labels, the labels are reused.
+const BASIN_LABELS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// Render the height map with each location labeled by its basin, and the 9s
+/// left blank. Basins are labeled in order of their low points.
+pub fn render_basins(input: &str, diagonals: bool) -> anyhow::Result<String> {
+    let grid: VecGrid<i32> = parse_digit_grid(input).context("failed to parse height map")?;
+    let basins = assign_basins(&grid, diagonals);
+
+    let labels: HashMap<BasinId, char> = basins
+        .values()
+        .copied()
+        .unique()
+        .sorted_by_key(|basin| (basin.low_point.row, basin.low_point.column))
+        .zip(BASIN_LABELS.chars().cycle())
+        .collect();
+
+    let label_grid = VecGrid::new_from_rows(grid.rows().iter().map(|row| {
+        row.iter_with_locations()
+            .map(|(location, _)| basins.get(&location).map(|basin| labels[basin]))
+            .collect::<Vec<_>>()
+    }))
+    .context("grid is too large")?;
+
+    Ok(render_grid(&label_grid, |label| label.unwrap_or(' ')))
+}
+
+pub fn part2(input: &str) -> anyhow::Result<usize> {
+    basin_sizes(input, false).map(|sizes| sizes.iter().take(3).product())
+}
+
+pub struct Solution;
+
+impl Solver for Solution {
+    fn part1(&self, input: &str) -> anyhow::Result<String> {
+        part1(input).map(|answer| answer.to_string())
+    }
+
+    fn part2(&self, input: &str) -> anyhow::Result<String> {
+        part2(input).map(|answer| answer.to_string())
+    }
+
+    fn details(&self, part: Part, input: &str) -> anyhow::Result<Option<String>> {
+        match part {
+            Part::Part1 => low_points(input, false).map(|low_points| {
+                Some(
+                    low_points
+                        .iter()
+                        .map(|(location, height)| {
+                            format!(
+                                "row {}, column {}: height {}",
+                                location.row.0, location.column.0, height
+                            )
+                        })
+                        .join("\n"),
+                )
+            }),
+            Part::Part2 => render_basins(input, false).map(Some),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+2199943210
+3987894921
+9856789892
+8767896789
+9899965678
+";
+
+    #[test]
+    fn test_sample() {
+        assert_eq!(part1(SAMPLE).unwrap(), 15);
+        assert_eq!(part2(SAMPLE).unwrap(), 1134);
+    }
+
+    #[test]
+    fn test_low_points() {
+        assert_eq!(
+            low_points(SAMPLE, false).unwrap(),
+            [
+                (Row(0) + Column(1), 1),
+                (Row(0) + Column(9), 0),
+                (Row(2) + Column(2), 5),
+                (Row(4) + Column(6), 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_basin_sizes() {
+        assert_eq!(basin_sizes(SAMPLE, false).unwrap(), [14, 9, 9, 3]);
+    }
+
+    #[test]
+    fn test_render_basins() {
+        let expected = [
+            "aa   bbbbb",
+            "a ccc b bb",
+            " ccccc d b",
+            "ccccc ddd ",
+            " c   ddddd",
+        ]
+        .join("\n");
+
+        assert_eq!(render_basins(SAMPLE, false).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_diagonals() {
+        // The 2 is only a low point if diagonals don't count
+        let input = "29\n91\n";
+
+        assert_eq!(total_risk(input, false).unwrap(), 5);
+        assert_eq!(total_risk(input, true).unwrap(), 2);
+
+        assert_eq!(basin_sizes(input, false).unwrap(), [1, 1]);
+        assert_eq!(basin_sizes(input, true).unwrap(), [2]);
+
+        // The sample's low points are low even counting diagonals
+        assert_eq!(total_risk(SAMPLE, true).unwrap(), 15);
+    }
+
+    #[test]
+    fn test_plateau() {
+        // A flat bottom is a single basin
+        let input = "9999999\n9555559\n9955599\n9999999\n";
+        assert_eq!(basin_sizes(input, false).unwrap(), [8]);
+        assert_eq!(
+            render_basins(input, false).unwrap().lines().nth(2),
+            Some("  aaa  ")
+        );
+
+        // A plateau with a way down drains as a whole
+        let input = "99999\n95554\n95559\n99999\n";
+        assert_eq!(basin_sizes(input, false).unwrap(), [7]);
+
+        // Separate plateaus of the same height are still separate
+        let input = "55955\n";
+        assert_eq!(basin_sizes(input, false).unwrap(), [2, 2]);
+    }
+
+    #[test]
+    fn test_long_descent() {
+        // A plateau of 1s snaking back and forth between walls of 9s, which
+        // drains into a single 0 at its far end. Every location on the path is
+        // tens of thousands of steps from the low point, which would overflow
+        // the stack if the path were followed recursively.
+        let width = 201;
+        let input = (0..width)
+            .map(|row| match row % 4 {
+                1 => "9".repeat(width - 1) + "1",
+                3 => "1".to_owned() + &"9".repeat(width - 1),
+                _ if row == width - 1 => "1".repeat(width - 1) + "0",
+                _ => "1".repeat(width),
+            })
+            .join("\n");
+
+        let grid: VecGrid<i32> = parse_digit_grid(&input).unwrap();
+        let mut basins = HashMap::new();
+        let low_point = Row(width as isize - 1) + Column(width as isize - 1);
+
+        assert_eq!(
+            identify_basin(Row(0) + Column(0), &grid, adjacencies(false), &mut basins),
+            BasinId { low_point }
+        );
+
+        let basin_size = 101 * width + 100;
+        assert_eq!(basins.len(), basin_size);
 
-    Ok(iter::from_fn(|| sorted_counts.pop()).take(3).product())
+        assert_eq!(part1(&input).unwrap(), 1);
+        assert_eq!(basin_sizes(&input, false).unwrap(), [basin_size]);
+    }
 }
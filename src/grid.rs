@@ -0,0 +1,301 @@
+use std::fmt::Display;
+
+use anyhow::{bail, ensure, Context};
+use gridly::prelude::*;
+use gridly_grids::{ArrayGrid, SparseGrid, VecGrid};
+use itertools::Itertools;
+
+/// Parse lines of single decimal digits into rows of cells, checking that
+/// every row has the same length.
+fn parse_digit_rows<T: From<u8>>(input: &str) -> anyhow::Result<Vec<Vec<T>>> {
+    let mut rows: Vec<Vec<T>> = Vec::new();
+
+    for (row, line) in input.lines().enumerate() {
+        let cells: Vec<T> = line
+            .chars()
+            .enumerate()
+            .map(|(column, c)| {
+                c.to_digit(10)
+                    .map(|digit| T::from(digit as u8))
+                    .with_context(|| {
+                        format!("invalid digit {:?} at row {}, column {}", c, row, column)
+                    })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        if let Some(first) = rows.first() {
+            ensure!(
+                cells.len() == first.len(),
+                "row {} has length {}, but row 0 has length {}",
+                row,
+                cells.len(),
+                first.len(),
+            );
+        }
+
+        rows.push(cells);
+    }
+
+    ensure!(!rows.is_empty(), "empty grid");
+
+    Ok(rows)
+}
+
+/// Parse a grid of single decimal digits, such as a height map
+pub fn parse_digit_grid<T: From<u8>>(input: &str) -> anyhow::Result<VecGrid<T>> {
+    let rows = parse_digit_rows(input)?;
+    VecGrid::new_from_rows(rows).context("grid is too large")
+}
+
+/// Parse a grid of single decimal digits with dimensions known at compile time
+pub fn parse_digit_array_grid<T: From<u8>, const R: usize, const C: usize>(
+    input: &str,
+) -> anyhow::Result<ArrayGrid<T, R, C>> {
+    let rows = parse_digit_rows(input)?;
+
+    if rows.len() != R || rows[0].len() != C {
+        bail!(
+            "grid is {}x{}, expected {}x{}",
+            rows.len(),
+            rows[0].len(),
+            R,
+            C
+        );
+    }
+
+    let rows = brownstone::build_iter(rows.into_iter().map(brownstone::build_iter));
+    Ok(ArrayGrid::from_rows(rows))
+}
+
+/// Iterate over the neighbors of `location` in each of `directions`, along
+/// with their cells, skipping any that are out of bounds
+pub fn neighbors<'a, G: Grid, D: VectorLike + Copy>(
+    grid: &'a G,
+    location: Location,
+    directions: &'a [D],
+) -> impl Iterator<Item = (Location, &'a G::Item)> + 'a {
+    directions
+        .iter()
+        .map(move |&direction| location + direction)
+        .filter_map(move |neighbor| grid.get(neighbor).ok().map(|cell| (neighbor, cell)))
+}
+
+/// Iterate over the in-bounds neighbors of `location`, including diagonals
+pub fn neighbors_with_diagonals<G: Grid>(
+    grid: &G,
+    location: Location,
+) -> impl Iterator<Item = (Location, &G::Item)> {
+    neighbors(grid, location, &TOUCHING_ADJACENCIES)
+}
+
+fn render_region<G: Grid>(
+    grid: &G,
+    rows: RowRange,
+    columns: ColumnRange,
+    cell_to_char: impl Fn(Location, &G::Item) -> char,
+) -> String {
+    rows.map(|row| {
+        columns
+            .clone()
+            .map(|column| row + column)
+            .map(|location| {
+                let cell = grid.get(location).expect("rendered out of bounds");
+                cell_to_char(location, cell)
+            })
+            .collect::<String>()
+    })
+    .join("\n")
+}
+
+/// Render a grid as one line of text per row
+pub fn render_grid<G: Grid>(grid: &G, cell_to_char: impl Fn(&G::Item) -> char) -> String {
+    render_grid_with_locations(grid, |_, cell| cell_to_char(cell))
+}
+
+/// Render a grid as one line of text per row, where the character for each
+/// cell can also depend on its location
+pub fn render_grid_with_locations<G: Grid>(
+    grid: &G,
+    cell_to_char: impl Fn(Location, &G::Item) -> char,
+) -> String {
+    render_region(grid, grid.row_range(), grid.column_range(), cell_to_char)
+}
+
+/// Get the row and column ranges of the smallest box containing every
+/// occupied (non-default) cell of a sparse grid, or None if it's empty
+fn occupied_bounds<T: Clone + PartialEq>(grid: &SparseGrid<T>) -> Option<(RowRange, ColumnRange)> {
+    let occupied = grid.occupied_entries().map(|(&location, _)| location);
+    let rows = occupied.clone().map(|location| location.row).minmax();
+    let columns = occupied.map(|location| location.column).minmax();
+
+    match (rows.into_option(), columns.into_option()) {
+        (Some((min_row, max_row)), Some((min_column, max_column))) => Some((
+            RowRange::bounded(min_row, max_row + Rows(1)),
+            ColumnRange::bounded(min_column, max_column + Columns(1)),
+        )),
+        _ => None,
+    }
+}
+
+/// Copy the occupied (non-default) cells of a sparse grid into a dense grid,
+/// trimmed to their bounding box, or None if the grid is empty
+pub fn trim_occupied<T: Clone + PartialEq>(grid: &SparseGrid<T>) -> Option<VecGrid<T>> {
+    let (rows, columns) = occupied_bounds(grid)?;
+
+    let cells = rows.map(|row| {
+        columns
+            .clone()
+            .map(|column| grid.get(row + column).expect("trimmed out of bounds"))
+            .cloned()
+            .collect_vec()
+    });
+
+    VecGrid::new_from_rows(cells)
+}
+
+/// Render a sparse grid as one line of text per row, trimmed to the bounding
+/// box of its occupied (non-default) cells
+pub fn render_occupied<T: Clone + PartialEq>(
+    grid: &SparseGrid<T>,
+    cell_to_char: impl Fn(&T) -> char,
+) -> String {
+    match occupied_bounds(grid) {
+        Some((rows, columns)) => render_region(grid, rows, columns, |_, cell| cell_to_char(cell)),
+        None => String::new(),
+    }
+}
+
+/// Render a grid of multi-character cells as one line of text per row, with
+/// the cells on each row joined by `separator`. Trailing whitespace is
+/// trimmed from each line.
+pub fn render_grid_cells<G: Grid, S: Display>(
+    grid: &G,
+    separator: &str,
+    render_cell: impl Fn(&G::Item) -> S,
+) -> String {
+    grid.row_range()
+        .map(|row| {
+            let line = grid
+                .column_range()
+                .map(|column| grid.get(row + column).expect("rendered out of bounds"))
+                .map(&render_cell)
+                .join(separator);
+
+            line.trim_end().to_owned()
+        })
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_digit_grid() {
+        let grid: VecGrid<i32> = parse_digit_grid("123\n456\n").unwrap();
+
+        assert_eq!(grid.dimensions(), Rows(2) + Columns(3));
+        assert_eq!(grid.get((1, 2)), Ok(&6));
+    }
+
+    #[test]
+    fn test_ragged_grid() {
+        let err = parse_digit_grid::<i32>("123\n45\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "row 1 has length 2, but row 0 has length 3"
+        );
+    }
+
+    #[test]
+    fn test_non_digit() {
+        let err = parse_digit_grid::<i32>("123\n4x6\n").unwrap_err();
+        assert_eq!(err.to_string(), "invalid digit 'x' at row 1, column 1");
+    }
+
+    #[test]
+    fn test_array_grid_wrong_size() {
+        assert!(parse_digit_array_grid::<i64, 3, 3>("12\n34\n").is_err());
+        assert!(parse_digit_array_grid::<i64, 2, 2>("12\n34\n").is_ok());
+    }
+
+    #[test]
+    fn test_neighbor_counts() {
+        let grid: VecGrid<i32> = parse_digit_grid("123\n456\n789").unwrap();
+        let corner = Location::zero();
+        let center = Location::new(1, 1);
+
+        assert_eq!(neighbors(&grid, corner, &EACH_DIRECTION).count(), 2);
+        assert_eq!(neighbors(&grid, center, &EACH_DIRECTION).count(), 4);
+        assert_eq!(neighbors_with_diagonals(&grid, corner).count(), 3);
+        assert_eq!(neighbors_with_diagonals(&grid, center).count(), 8);
+    }
+
+    #[test]
+    fn test_neighbor_cells() {
+        let grid: VecGrid<i32> = parse_digit_grid("123\n456\n789").unwrap();
+
+        let mut cells: Vec<i32> = neighbors(&grid, Location::zero(), &EACH_DIRECTION)
+            .map(|(_, &cell)| cell)
+            .collect();
+        cells.sort_unstable();
+
+        assert_eq!(cells, [2, 4]);
+    }
+
+    #[test]
+    fn test_render_grid() {
+        let grid: VecGrid<i32> = parse_digit_grid("120\n301").unwrap();
+        let rendered = render_grid(&grid, |&cell| match cell {
+            0 => '.',
+            _ => '#',
+        });
+
+        assert_eq!(rendered, "##.\n#.#");
+    }
+
+    #[test]
+    fn test_render_occupied() {
+        let mut grid = SparseGrid::new_default(Rows(10) + Columns(10), false);
+        grid.insert((2, 3), true);
+        grid.insert((3, 5), true);
+
+        let rendered = render_occupied(&grid, |&dot| if dot { '#' } else { '.' });
+        assert_eq!(rendered, "#..\n..#");
+    }
+
+    #[test]
+    fn test_trim_occupied() {
+        let mut grid = SparseGrid::new_default(Rows(10) + Columns(10), 0);
+        grid.insert((2, 3), 1);
+        grid.insert((3, 5), 2);
+
+        let trimmed = trim_occupied(&grid).unwrap();
+        assert_eq!(trimmed.dimensions(), Rows(2) + Columns(3));
+        assert_eq!(trimmed.get((0, 0)), Ok(&1));
+        assert_eq!(trimmed.get((1, 2)), Ok(&2));
+        assert_eq!(trimmed.get((1, 0)), Ok(&0));
+        assert!(trim_occupied(&SparseGrid::new_default(Rows(3) + Columns(3), 0)).is_none());
+    }
+
+    #[test]
+    fn test_render_grid_with_locations() {
+        let grid: VecGrid<i32> = parse_digit_grid("12\n34").unwrap();
+        let rendered = render_grid_with_locations(&grid, |location, &cell| {
+            match location == Location::zero() {
+                true => '*',
+                false => char::from_digit(cell as u32, 10).unwrap(),
+            }
+        });
+
+        assert_eq!(rendered, "*2\n34");
+    }
+
+    #[test]
+    fn test_render_grid_cells() {
+        let grid: VecGrid<i32> = parse_digit_grid("12\n34").unwrap();
+        let rendered = render_grid_cells(&grid, ", ", |&cell| cell * 10);
+
+        assert_eq!(rendered, "10, 20\n30, 40");
+    }
+}
@@ -1,7 +1,7 @@
 use std::{
     cell::UnsafeCell,
     cmp,
-    collections::{hash_map, HashMap},
+    collections::{hash_map, HashMap, VecDeque},
     hash::Hash,
     iter::FusedIterator,
     mem, ops,
@@ -10,6 +10,11 @@ use std::{
 };
 
 use enum_map::MaybeUninit;
+use itertools::Itertools;
+use nom_supreme::{
+    error::{BaseErrorKind, ErrorTree, StackContext},
+    final_parser::Location,
+};
 use num::Num;
 use rayon::prelude::*;
 use thiserror::Error;
@@ -91,6 +96,49 @@ where
     }
 }
 
+/// Like `Windows`, but with a window size chosen at runtime. Because the
+/// windows can't be returned as arrays, only the first and last items of each
+/// window are yielded.
+#[derive(Debug, Clone)]
+pub struct WindowEnds<I: Iterator> {
+    iter: I,
+    buffer: VecDeque<I::Item>,
+    size: usize,
+}
+
+impl<I: Iterator> Iterator for WindowEnds<I>
+where
+    I::Item: Clone,
+{
+    type Item = (I::Item, I::Item);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let next = self.iter.next()?;
+            self.buffer.push_back(next.clone());
+
+            if self.buffer.len() == self.size {
+                let first = self.buffer.pop_front()?;
+                return Some((first, next));
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Every item from the underlying iterator completes a window, once
+        // the buffer is full
+        let missing = self.size - 1 - self.buffer.len();
+        let (min, max) = self.iter.size_hint();
+
+        (
+            min.saturating_sub(missing),
+            max.map(|max| max.saturating_sub(missing)),
+        )
+    }
+}
+
+impl<I: FusedIterator> FusedIterator for WindowEnds<I> where I::Item: Clone {}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Chunks<I, const N: usize> {
     iter: I,
@@ -119,7 +167,7 @@ impl<I: DoubleEndedIterator + ExactSizeIterator, const N: usize> DoubleEndedIter
     for Chunks<I, N>
 {
     fn next_back(&mut self) -> Option<Self::Item> {
-        while self.iter.len() % N != 0 {
+        while !self.iter.len().is_multiple_of(N) {
             let _ = self.iter.next_back();
         }
 
@@ -178,6 +226,21 @@ pub trait IterExt: Iterator + Sized {
         }
     }
 
+    /// Get the first and last items of each window of `size` consecutive
+    /// items, without collecting the whole iterator. Panics if `size` is 0.
+    fn streaming_window_ends(self, size: usize) -> WindowEnds<Self>
+    where
+        Self::Item: Clone,
+    {
+        assert!(size > 0, "window size must be at least 1");
+
+        WindowEnds {
+            iter: self,
+            buffer: VecDeque::with_capacity(size),
+            size,
+        }
+    }
+
     fn streaming_chunks<const N: usize>(self) -> Chunks<Self, N> {
         Chunks { iter: self }
     }
@@ -204,7 +267,9 @@ impl<I: Iterator> IterExt for I {}
 mod iter_ext_tests {
     use super::*;
 
+    // The map is what infers the window size
     #[test]
+    #[allow(clippy::map_identity)]
     fn test_streaming_windows() {
         assert!((0..6).streaming_windows().map(|[a, b, c]| [a, b, c]).eq([
             [0, 1, 2],
@@ -253,6 +318,24 @@ mod iter_ext_tests {
         assert_eq!(windows.size_hint(), (0, Some(0)));
         assert_eq!(windows.next(), None);
     }
+
+    #[test]
+    fn test_streaming_window_ends() {
+        let mut ends = (0..6).streaming_window_ends(3);
+
+        assert_eq!(ends.size_hint(), (4, Some(4)));
+        assert_eq!(ends.next(), Some((0, 2)));
+        assert_eq!(ends.size_hint(), (3, Some(3)));
+        assert!(ends.eq([(1, 3), (2, 4), (3, 5)]));
+
+        assert!((0..3).streaming_window_ends(1).eq([(0, 0), (1, 1), (2, 2)]));
+        assert_eq!((0..3).streaming_window_ends(4).next(), None);
+
+        // The ends match the ends of the fixed-size windows
+        assert!((0..10)
+            .streaming_window_ends(4)
+            .eq((0..10).streaming_windows().map(|[a, _, _, d]| (a, d))));
+    }
 }
 
 pub trait StrExt {
@@ -275,6 +358,18 @@ pub struct ParseListError<E> {
     error: E,
 }
 
+pub fn parse_tokens<'a, T: FromStr, I: IntoIterator<Item = &'a str>>(
+    input: I,
+) -> impl Iterator<Item = Result<T, ParseListError<T::Err>>> + use<'a, T, I> {
+    input.into_iter().enumerate().map(|(index, token)| {
+        token.parse().map_err(|error| ParseListError {
+            token: token.to_string(),
+            index,
+            error,
+        })
+    })
+}
+
 pub fn parse_input_iter<'a, T, C>(
     input: impl IntoIterator<Item = &'a str>,
 ) -> Result<C, ParseListError<T::Err>>
@@ -282,25 +377,111 @@ where
     T: FromStr,
     C: FromIterator<T>,
 {
-    input
-        .into_iter()
-        .enumerate()
-        .map(|(index, token)| {
-            token.parse().map_err(|error| ParseListError {
-                token: token.to_string(),
-                index,
-                error,
-            })
+    parse_tokens(input).collect()
+}
+
+struct ParseFailure<'a> {
+    location: Location,
+    kind: &'a BaseErrorKind,
+
+    // Innermost context first
+    contexts: Vec<&'a StackContext>,
+}
+
+fn collect_parse_failures<'a>(
+    err: &'a ErrorTree<Location>,
+    contexts: &mut Vec<&'a StackContext>,
+    failures: &mut Vec<ParseFailure<'a>>,
+) {
+    match err {
+        ErrorTree::Base { location, kind } => failures.push(ParseFailure {
+            location: *location,
+            kind,
+            contexts: contexts.iter().rev().copied().collect(),
+        }),
+        ErrorTree::Stack {
+            base,
+            contexts: stack,
+        } => {
+            // The stack is stored innermost first, but `contexts` is
+            // outermost first so that it can be truncated after recursing
+            let depth = contexts.len();
+            contexts.extend(stack.iter().rev().map(|(_, context)| context));
+            collect_parse_failures(base, contexts, failures);
+            contexts.truncate(depth);
+        }
+        ErrorTree::Alt(branches) => branches
+            .iter()
+            .for_each(|branch| collect_parse_failures(branch, contexts, failures)),
+    }
+}
+
+/// Render an error from a final parser as a readable diagnostic. The error
+/// is reported at the furthest point any branch of the parser reached,
+/// showing that line of the input with a caret under the offending column,
+/// followed by the sections that were being parsed at the time.
+pub fn format_parse_error(input: &str, err: &ErrorTree<Location>) -> String {
+    let mut failures = Vec::new();
+    collect_parse_failures(err, &mut Vec::new(), &mut failures);
+
+    let furthest = failures
+        .iter()
+        .map(|failure| (failure.location.line, failure.location.column))
+        .max()
+        .unwrap_or((1, 1));
+
+    let failures = failures
+        .iter()
+        .filter(|failure| (failure.location.line, failure.location.column) == furthest)
+        .collect_vec();
+
+    let (line_number, column) = furthest;
+    let line = input.lines().nth(line_number - 1).unwrap_or("");
+
+    // Location columns count bytes; count characters to line up the caret
+    let offset = line
+        .get(..column - 1)
+        .map(|prefix| prefix.chars().count())
+        .unwrap_or(column - 1);
+
+    // External errors, such as from `map_res`, carry their own message
+    let expected = failures
+        .iter()
+        .map(|failure| match failure.kind {
+            BaseErrorKind::External(err) => err.to_string(),
+            kind => kind.to_string(),
         })
-        .collect()
+        .unique()
+        .join(", or ");
+
+    let mut message = format!(
+        "{} at line {}, column {}\n    {}\n    {:offset$}^",
+        expected,
+        line_number,
+        column,
+        line,
+        "",
+        offset = offset,
+    );
+
+    if let Some(failure) = failures.first() {
+        failure
+            .contexts
+            .iter()
+            .for_each(|context| message.push_str(&format!("\n  {}", context)));
+    }
+
+    message
 }
 
+/// Count how many times each item appears. The count type defaults to
+/// `usize`, but can be something wider for counts that might overflow it.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Counter<T: Eq + Hash> {
-    counts: HashMap<T, usize>,
+pub struct Counter<T: Eq + Hash, N = usize> {
+    counts: HashMap<T, N>,
 }
 
-impl<T: Eq + Hash> Default for Counter<T> {
+impl<T: Eq + Hash, N> Default for Counter<T, N> {
     fn default() -> Self {
         Self {
             counts: Default::default(),
@@ -308,32 +489,36 @@ impl<T: Eq + Hash> Default for Counter<T> {
     }
 }
 
-impl<T: Eq + Hash> Counter<T> {
+impl<T: Eq + Hash, N: Num + Copy> Counter<T, N> {
     pub fn new() -> Self {
         Self {
             counts: HashMap::new(),
         }
     }
 
-    pub fn add(&mut self, value: T, additional: usize) {
-        if additional > 0 {
+    pub fn add(&mut self, value: T, additional: N) {
+        if !additional.is_zero() {
             self.counts
                 .entry(value)
-                .and_modify(|value| *value += additional)
+                .and_modify(|value| *value = *value + additional)
                 .or_insert(additional);
         }
     }
 
     pub fn add_one(&mut self, value: T) {
-        self.add(value, 1)
+        self.add(value, N::one())
     }
 
-    pub fn iter_counts(
-        &self,
-    ) -> impl Iterator<Item = (&T, usize)> + Clone + FusedIterator + ExactSizeIterator {
+    pub fn iter_counts(&self) -> impl FusedIterator<Item = (&T, N)> + ExactSizeIterator + Clone {
         self.counts.iter().map(|(item, &count)| (item, count))
     }
 
+    pub fn total(&self) -> N {
+        self.counts
+            .values()
+            .fold(N::zero(), |total, &count| total + count)
+    }
+
     pub fn merge(self, other: Self) -> Self {
         let (mut receiver, sender) = match self.counts.len().cmp(&other.counts.len()) {
             cmp::Ordering::Less => (other, self),
@@ -349,26 +534,26 @@ impl<T: Eq + Hash> Counter<T> {
     }
 }
 
-impl<T: Eq + Hash + Sync> Counter<T> {
-    pub fn par_iter_counts(&self) -> impl ParallelIterator<Item = (&T, usize)> {
+impl<T: Eq + Hash + Sync, N: Num + Copy + Send + Sync> Counter<T, N> {
+    pub fn par_iter_counts(&self) -> impl ParallelIterator<Item = (&T, N)> {
         self.counts.par_iter().map(|(item, &count)| (item, count))
     }
 }
 
-impl<T: Eq + Hash> Extend<T> for Counter<T> {
+impl<T: Eq + Hash, N: Num + Copy> Extend<T> for Counter<T, N> {
     fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
-        self.extend(iter.into_iter().map(|item| (item, 1)))
+        self.extend(iter.into_iter().map(|item| (item, N::one())))
     }
 }
 
-impl<T: Eq + Hash> Extend<(T, usize)> for Counter<T> {
-    fn extend<I: IntoIterator<Item = (T, usize)>>(&mut self, iter: I) {
+impl<T: Eq + Hash, N: Num + Copy> Extend<(T, N)> for Counter<T, N> {
+    fn extend<I: IntoIterator<Item = (T, N)>>(&mut self, iter: I) {
         iter.into_iter()
             .for_each(|(item, count)| self.add(item, count))
     }
 }
 
-impl<T: Eq + Hash> ops::Add<Self> for Counter<T> {
+impl<T: Eq + Hash, N: Num + Copy> ops::Add<Self> for Counter<T, N> {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
@@ -376,25 +561,25 @@ impl<T: Eq + Hash> ops::Add<Self> for Counter<T> {
     }
 }
 
-impl<T: Eq + Hash> ops::AddAssign<Self> for Counter<T> {
+impl<T: Eq + Hash, N: Num + Copy> ops::AddAssign<Self> for Counter<T, N> {
     fn add_assign(&mut self, rhs: Self) {
         *self = mem::take(self).merge(rhs);
     }
 }
 
-impl<T: Eq + Hash + Send> ParallelExtend<T> for Counter<T> {
+impl<T: Eq + Hash + Send, N: Num + Copy + Send> ParallelExtend<T> for Counter<T, N> {
     fn par_extend<I>(&mut self, par_iter: I)
     where
         I: rayon::iter::IntoParallelIterator<Item = T>,
     {
-        self.par_extend(par_iter.into_par_iter().map(|item| (item, 1)))
+        self.par_extend(par_iter.into_par_iter().map(|item| (item, N::one())))
     }
 }
 
-impl<T: Eq + Hash + Send> ParallelExtend<(T, usize)> for Counter<T> {
+impl<T: Eq + Hash + Send, N: Num + Copy + Send> ParallelExtend<(T, N)> for Counter<T, N> {
     fn par_extend<I>(&mut self, par_iter: I)
     where
-        I: rayon::iter::IntoParallelIterator<Item = (T, usize)>,
+        I: rayon::iter::IntoParallelIterator<Item = (T, N)>,
     {
         let this = AtomicCell::new(mem::take(self));
 
@@ -411,19 +596,22 @@ impl<T: Eq + Hash + Send> ParallelExtend<(T, usize)> for Counter<T> {
     }
 }
 
-impl<T: Eq + Hash + Send> FromParallelIterator<T> for Counter<T> {
+impl<T: Eq + Hash + Send, N: Num + Copy + Send> FromParallelIterator<T> for Counter<T, N> {
     fn from_par_iter<I>(par_iter: I) -> Self
     where
         I: IntoParallelIterator<Item = T>,
     {
-        par_iter.into_par_iter().map(|item| (item, 1)).collect()
+        par_iter
+            .into_par_iter()
+            .map(|item| (item, N::one()))
+            .collect()
     }
 }
 
-impl<T: Eq + Hash + Send> FromParallelIterator<(T, usize)> for Counter<T> {
+impl<T: Eq + Hash + Send, N: Num + Copy + Send> FromParallelIterator<(T, N)> for Counter<T, N> {
     fn from_par_iter<I>(par_iter: I) -> Self
     where
-        I: IntoParallelIterator<Item = (T, usize)>,
+        I: IntoParallelIterator<Item = (T, N)>,
     {
         par_iter
             .into_par_iter()
@@ -435,7 +623,7 @@ impl<T: Eq + Hash + Send> FromParallelIterator<(T, usize)> for Counter<T> {
     }
 }
 
-impl<T: Eq + Hash, U> FromIterator<U> for Counter<T>
+impl<T: Eq + Hash, N: Num + Copy, U> FromIterator<U> for Counter<T, N>
 where
     Self: Extend<U>,
 {
@@ -446,16 +634,62 @@ where
     }
 }
 
-impl<T: Eq + Hash> IntoIterator for Counter<T> {
-    type Item = (T, usize);
+impl<T: Eq + Hash, N> IntoIterator for Counter<T, N> {
+    type Item = (T, N);
 
-    type IntoIter = hash_map::IntoIter<T, usize>;
+    type IntoIter = hash_map::IntoIter<T, N>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.counts.into_iter()
     }
 }
 
+#[cfg(test)]
+mod counter_tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn additions() -> impl Strategy<Value = Vec<(u8, usize)>> {
+        prop::collection::vec((any::<u8>(), 0..5usize), 0..64)
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(256))]
+
+        #[test]
+        fn test_no_zero_counts(additions in additions()) {
+            let counter: Counter<u8> = additions.into_iter().collect();
+            prop_assert!(counter.iter_counts().all(|(_, count)| count > 0));
+        }
+
+        #[test]
+        fn test_total(additions in additions()) {
+            let expected: usize = additions.iter().map(|&(_, count)| count).sum();
+            let counter: Counter<u8> = additions.into_iter().collect();
+            prop_assert_eq!(counter.total(), expected);
+        }
+
+        #[test]
+        fn test_merge_commutes(lhs in additions(), rhs in additions()) {
+            let lhs: Counter<u8> = lhs.into_iter().collect();
+            let rhs: Counter<u8> = rhs.into_iter().collect();
+
+            prop_assert_eq!(lhs.clone().merge(rhs.clone()), rhs.merge(lhs));
+        }
+
+        #[test]
+        fn test_parallel_matches_sequential(additions in additions()) {
+            let sequential: Counter<u8> = additions.iter().copied().collect();
+            let parallel: Counter<u8> = additions.par_iter().copied().collect();
+            prop_assert_eq!(&parallel, &sequential);
+
+            let mut extended: Counter<u8> = Counter::new();
+            extended.par_extend(additions.par_iter().copied());
+            prop_assert_eq!(&extended, &sequential);
+        }
+    }
+}
+
 struct AtomicCell<T> {
     inhabited: AtomicBool,
     value: UnsafeCell<MaybeUninit<T>>,
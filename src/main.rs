@@ -1,5 +1,6 @@
 include!(concat!(env!("OUT_DIR"), "/generated.rs"));
 
+mod grid;
 mod library;
 
 use std::{
@@ -10,7 +11,7 @@ use std::{
     str::FromStr,
 };
 
-use anyhow::Context;
+use anyhow::{ensure, Context};
 use structopt::StructOpt;
 use thiserror::Error;
 
@@ -24,7 +25,7 @@ pub enum DayError {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Part {
+pub enum Part {
     Part1,
     Part2,
 }
@@ -38,6 +39,19 @@ pub enum PartError {
     BadPart(u8),
 }
 
+impl FromStr for Day {
+    type Err = DayError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value: u8 = s.parse()?;
+
+        DAYS.iter()
+            .copied()
+            .find(|&day| day as u8 == value)
+            .ok_or(DayError::BadDay(value))
+    }
+}
+
 impl FromStr for Part {
     type Err = PartError;
 
@@ -52,15 +66,69 @@ impl FromStr for Part {
     }
 }
 
+/// A solution to a single advent of code day. Each day module has a
+/// `Solution` type implementing this by forwarding to its `part1` and `part2`.
+pub trait Solver {
+    fn part1(&self, input: &str) -> anyhow::Result<String>;
+    fn part2(&self, input: &str) -> anyhow::Result<String>;
+
+    /// Solve both parts. Days can override this when the parts share most of
+    /// their work.
+    fn both(&self, input: &str) -> anyhow::Result<(String, String)> {
+        Ok((self.part1(input)?, self.part2(input)?))
+    }
+
+    /// Extra details about the solution to a part, printed after the answer
+    /// with `--verbose`. Most days don't have any.
+    fn details(&self, _part: Part, _input: &str) -> anyhow::Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+fn run_solution(day: Day, part: Option<Part>, input: &str, verbose: bool) -> anyhow::Result<()> {
+    let solver = solver_for(day);
+
+    let answers = match part {
+        Some(part) => {
+            let answer = match part {
+                Part::Part1 => solver.part1(input),
+                Part::Part2 => solver.part2(input),
+            }
+            .context("failed to solve puzzle")?;
+
+            vec![(part, answer)]
+        }
+        None => {
+            let (part1, part2) = solver.both(input).context("failed to solve puzzle")?;
+            vec![(Part::Part1, part1), (Part::Part2, part2)]
+        }
+    };
+
+    for (part, answer) in answers {
+        println!("{}", answer);
+
+        if verbose {
+            if let Some(details) = solver
+                .details(part, input)
+                .context("failed to get solution details")?
+            {
+                println!("{}", details);
+            }
+        }
+    }
+
+    Ok(())
+}
+
 #[derive(StructOpt)]
 struct Args {
     /// The advent of code day to solve
     #[structopt(short, long)]
     day: Day,
 
-    /// Which part of the day to solve
+    /// Which part of the day to solve. If omitted, solve both parts.
     #[structopt(short, long)]
-    part: Part,
+    part: Option<Part>,
 
     /// If given, read input from this file
     #[structopt(short, long, conflicts_with = "string")]
@@ -69,6 +137,30 @@ struct Args {
     /// If given, use this as the puzzle input directly
     #[structopt(short, long, conflicts_with = "file")]
     string: Option<String>,
+
+    /// For day 1, compare sums over a sliding window of this many readings,
+    /// instead of the window size used by the part
+    #[structopt(short, long)]
+    window: Option<usize>,
+
+    /// For day 3, which bit ("ones" or "zeros") wins a tied column while
+    /// finding the life support ratings, instead of ones like the puzzle
+    #[structopt(long, conflicts_with_all = &["part", "window", "steps"])]
+    tie_break: Option<day3::TieBreak>,
+
+    /// For day 14, apply the insertion rules this many times, instead of the
+    /// step count used by the part
+    #[structopt(long, conflicts_with_all = &["part", "window"])]
+    steps: Option<usize>,
+
+    /// For day 14 with --steps, leave pairs with no matching insertion rule
+    /// unchanged, instead of failing
+    #[structopt(long, requires = "steps")]
+    lenient: bool,
+
+    /// Print extra details about the solution, for days that have them
+    #[structopt(short, long)]
+    verbose: bool,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -91,5 +183,85 @@ fn main() -> anyhow::Result<()> {
         }
     };
 
-    run_solution(args.day, args.part, &buf)
+    if let Some(tie_break) = args.tie_break {
+        ensure!(
+            args.day == Day::Day3,
+            "--tie-break is only supported for day 3"
+        );
+
+        let diagnostics =
+            day3::diagnostics_with(&buf, tie_break).context("failed to solve puzzle")?;
+        println!("{}", diagnostics.life_support_rating());
+
+        if args.verbose {
+            println!("{}", diagnostics);
+        }
+
+        return Ok(());
+    }
+
+    if let Some(steps) = args.steps {
+        ensure!(
+            args.day == Day::Day14,
+            "--steps is only supported for day 14"
+        );
+
+        let strictness = match args.lenient {
+            true => day14::Strictness::Lenient,
+            false => day14::Strictness::Strict,
+        };
+
+        let answer = day14::solve(&buf, steps, strictness).context("failed to solve puzzle")?;
+        println!("{}", answer);
+
+        if args.verbose {
+            let details = day14::describe(&buf, steps, strictness)
+                .context("failed to get solution details")?;
+            println!("{}", details);
+        }
+
+        return Ok(());
+    }
+
+    match args.window {
+        Some(window) => {
+            ensure!(
+                args.day == Day::Day1,
+                "--window is only supported for day 1"
+            );
+
+            let stats = day1::window_stats(&buf, window).context("failed to solve puzzle")?;
+            println!("{}", stats.increases);
+
+            if args.verbose {
+                println!("{}", stats);
+            }
+
+            Ok(())
+        }
+        None => run_solution(args.day, args.part, &buf, args.verbose),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_input_is_an_error() {
+        for &day in DAYS {
+            let solver = solver_for(day);
+
+            assert!(
+                solver.part1("").is_err(),
+                "{:?} part 1 accepted empty input",
+                day
+            );
+            assert!(
+                solver.part2("").is_err(),
+                "{:?} part 2 accepted empty input",
+                day
+            );
+        }
+    }
 }
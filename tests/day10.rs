@@ -0,0 +1,32 @@
+use std::process::Command;
+
+const SAMPLE: &str = "\
+[({(<(())[]>[[{[]{<()<>>
+[(()[<>])]({[<{<<[]>>(
+{([(<{}[<>[]}>{[]{[(<()>
+(((({<>}<{<{<>}{[]{[]{}
+[[<[([]))<([[{}[[()]]]
+[{[{({}]{}}([{[{{{}}([]
+{<[[]]>}<{[{[{[]{()[[[]
+[<(<(<(<{}))><([]([]()
+<{([([[(<>()){}]>(<<{{
+<{([{{}}[<[[[<>{}]]]>[]]
+";
+
+#[test]
+fn test_blank_lines_are_quiet() {
+    // Follow every line of the sample with a blank and a whitespace-only line
+    let spaced: String = SAMPLE
+        .lines()
+        .map(|line| format!("{}\n\n   \n", line))
+        .collect();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_advent2021"))
+        .args(["--day", "10", "--string", &spaced])
+        .output()
+        .expect("failed to run advent2021");
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "26397\n288957\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}